@@ -0,0 +1,645 @@
+// src/discord_sink.rs
+
+use reqwest::{Method, StatusCode, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+
+use crate::notifications::{NotificationSink, StreamNotification};
+use crate::settings::DiscordSinkConfig;
+use crate::stream_state::{format_duration, EventKind};
+use crate::twitch_api::Stream;
+
+/// Twitch purple for live streams, grey once they ended.
+const LIVE_COLOR: u32 = 0x9146FF;
+const OFFLINE_COLOR: u32 = 0x747F8D;
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notifications waiting for the worker.
+const CHANNEL_CAPACITY: usize = 100;
+
+/// Work for the worker, handled in order.
+enum Job {
+    Notify(Box<StreamNotification>),
+    /// A stream ended; its message turns grey if no offline notification did that.
+    /// Sent when the stream ends, which may be before a held offline notification.
+    Ended {
+        user_id: String,
+    },
+}
+
+/// Checks a Discord sink's settings when the config is loaded.
+pub fn validate(config: &DiscordSinkConfig) -> Result<(), String> {
+    Url::parse(&config.webhook_url)
+        .map(|_| ())
+        .map_err(|e| format!("invalid Discord webhook_url: {}", e))
+}
+
+/// Posts notifications to a Discord channel webhook as embeds.
+///
+/// A go-live creates a message; later events for the same stream (game and title
+/// changes, the stream ending) edit that message instead of posting new ones.
+pub struct DiscordSink {
+    tx: mpsc::Sender<Job>,
+    worker: JoinHandle<()>,
+}
+
+impl DiscordSink {
+    /// Starts the worker that talks to Discord. Must be called from within the Tokio runtime.
+    pub fn new(config: &DiscordSinkConfig) -> Self {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let worker = DiscordWorker {
+            client: reqwest::Client::new(),
+            // Checked by `validate` when the config was loaded
+            webhook_url: Url::parse(&config.webhook_url).expect("webhook_url was validated"),
+            config: config.clone(),
+            messages: HashMap::new(),
+        };
+        let worker = tokio::spawn(worker.run(rx));
+        Self { tx, worker }
+    }
+}
+
+impl NotificationSink for DiscordSink {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn send(&self, notification: &StreamNotification) {
+        if let Err(e) = self
+            .tx
+            .try_send(Job::Notify(Box::new(notification.clone())))
+        {
+            warn!("(Discord) Dropping notification, worker is behind: {}", e);
+        }
+    }
+
+    fn stream_ended(&self, stream: &Stream) {
+        let job = Job::Ended {
+            user_id: stream.user_id.clone(),
+        };
+        if self.tx.try_send(job).is_err() {
+            warn!("(Discord) Worker is behind, message of ended stream stays live");
+        }
+    }
+
+    /// Closing the channel lets the worker finish the queued messages and stop.
+    fn shutdown(self: Box<Self>) -> Vec<JoinHandle<()>> {
+        let Self { worker, .. } = *self;
+        vec![worker]
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookMessage {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RateLimited {
+    retry_after: f64,
+}
+
+/// The result of a request to the webhook.
+enum Outcome {
+    /// Carries the message for `?wait=true` posts.
+    Sent(Option<WebhookMessage>),
+    /// The message to edit was deleted.
+    NotFound,
+    Failed,
+}
+
+/// The message posted for a session and the notification it currently shows.
+struct PostedMessage {
+    id: String,
+    notification: StreamNotification,
+}
+
+struct DiscordWorker {
+    client: reqwest::Client,
+    webhook_url: Url,
+    config: DiscordSinkConfig,
+    // The message of each stream's latest session, keyed by user ID; dropped once
+    // the offline summary has been delivered
+    messages: HashMap<String, PostedMessage>,
+}
+
+impl DiscordWorker {
+    async fn run(mut self, mut rx: mpsc::Receiver<Job>) {
+        // One at a time, so an edit never overtakes the post that created its message
+        while let Some(job) = rx.recv().await {
+            match job {
+                Job::Notify(notification) => self.handle(&notification).await,
+                Job::Ended { user_id } => self.end(&user_id).await,
+            }
+        }
+    }
+
+    async fn handle(&mut self, n: &StreamNotification) {
+        let user_id = &n.stream.user_id;
+        let existing = match n.kind {
+            // A new session always gets a new message
+            EventKind::Live => None,
+            _ => self.messages.get(user_id).map(|m| m.id.clone()),
+        };
+
+        if let Some(message_id) = existing {
+            match self.edit(&message_id, embed(n)).await {
+                Outcome::NotFound => {
+                    debug!("(Discord) Message was deleted, posting a new one");
+                }
+                _ => {
+                    if n.kind == EventKind::Offline {
+                        self.messages.remove(user_id);
+                    } else if let Some(message) = self.messages.get_mut(user_id) {
+                        message.notification = n.clone();
+                    }
+                    return;
+                }
+            }
+        }
+
+        if let Outcome::Sent(Some(message)) = self.post(n).await {
+            if n.kind == EventKind::Offline {
+                self.messages.remove(user_id);
+            } else {
+                let message = PostedMessage {
+                    id: message.id,
+                    notification: n.clone(),
+                };
+                self.messages.insert(user_id.clone(), message);
+            }
+        }
+    }
+
+    /// Greys out the message of a stream that ended. An offline notification has
+    /// already replaced it with the session summary, if this sink received one.
+    /// The message is kept: an offline notification held back by quiet hours still
+    /// edits it when the digest goes out, and the next go-live replaces it anyway.
+    async fn end(&mut self, user_id: &str) {
+        let Some(message) = self.messages.get(user_id) else {
+            return;
+        };
+        let mut embed = embed(&message.notification);
+        embed["color"] = json!(OFFLINE_COLOR);
+        embed["footer"] = json!({ "text": "Stream ended" });
+        // The preview would show a stream that is over
+        if let Some(fields) = embed.as_object_mut() {
+            fields.remove("image");
+        }
+        if let Outcome::NotFound = self.edit(&message.id, embed).await {
+            debug!("(Discord) Message of ended stream was deleted");
+            self.messages.remove(user_id);
+        }
+    }
+
+    /// The role to mention for a streamer, if any.
+    fn role_for(&self, login: &str) -> Option<&str> {
+        self.config
+            .role_mentions
+            .iter()
+            .find(|(l, _)| l.eq_ignore_ascii_case(login))
+            .map(|(_, role)| role.as_str())
+            .or(self.config.mention_role.as_deref())
+    }
+
+    async fn post(&self, n: &StreamNotification) -> Outcome {
+        let mut url = self.webhook_url.clone();
+        url.query_pairs_mut().append_pair("wait", "true");
+
+        // Only announcements ping; the end of a stream doesn't
+        let role = self
+            .role_for(&n.stream.user_login)
+            .filter(|_| n.kind != EventKind::Offline);
+        let mut body = json!({
+            "embeds": [embed(n)],
+            "allowed_mentions": { "parse": [], "roles": role.into_iter().collect::<Vec<_>>() },
+        });
+        if let Some(role) = role {
+            body["content"] = json!(format!("<@&{}>", role));
+        }
+        if let Some(username) = &self.config.username {
+            body["username"] = json!(username);
+        }
+        if let Some(avatar_url) = &self.config.avatar_url {
+            body["avatar_url"] = json!(avatar_url);
+        }
+        self.request(Method::POST, url, &body).await
+    }
+
+    async fn edit(&self, message_id: &str, embed: Value) -> Outcome {
+        let mut url = self.webhook_url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.push("messages").push(message_id);
+        }
+        let body = json!({ "embeds": [embed] });
+        self.request(Method::PATCH, url, &body).await
+    }
+
+    /// Sends a request, retrying on rate limits, server errors and network errors.
+    async fn request(&self, method: Method, url: Url, body: &Value) -> Outcome {
+        let mut delay = INITIAL_RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let response = self
+                .client
+                .request(method.clone(), url.clone())
+                .timeout(REQUEST_TIMEOUT)
+                .json(body)
+                .send()
+                .await;
+
+            match response {
+                Ok(response) if response.status() == StatusCode::NO_CONTENT => {
+                    return Outcome::Sent(None);
+                }
+                Ok(response) if response.status().is_success() => {
+                    return Outcome::Sent(response.json().await.ok());
+                }
+                Ok(response)
+                    if response.status() == StatusCode::NOT_FOUND && method == Method::PATCH =>
+                {
+                    return Outcome::NotFound;
+                }
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    let retry_after = response
+                        .json::<RateLimited>()
+                        .await
+                        .map(|r| Duration::from_secs_f64(r.retry_after.max(0.0)))
+                        .unwrap_or(delay);
+                    warn!(
+                        attempt,
+                        "(Discord) Rate limited, retrying in {:?}", retry_after
+                    );
+                    tokio::time::sleep(retry_after).await;
+                    continue;
+                }
+                Ok(response) if response.status().is_server_error() => {
+                    warn!(attempt, status = %response.status(), "(Discord) Server error");
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let message = response.text().await.unwrap_or_default();
+                    error!(status = %status, "(Discord) Request rejected: {}", message);
+                    return Outcome::Failed;
+                }
+                Err(e) => warn!(attempt, "(Discord) Request failed: {}", e),
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        error!("(Discord) Giving up after {} attempts", MAX_ATTEMPTS);
+        Outcome::Failed
+    }
+}
+
+/// Discord rejects empty field values.
+fn or_unknown(value: &str) -> &str {
+    if value.is_empty() {
+        "Unknown"
+    } else {
+        value
+    }
+}
+
+fn embed(n: &StreamNotification) -> Value {
+    let stream = &n.stream;
+    let mut embed = json!({
+        "title": n.summary,
+        "url": n.url,
+        "description": stream.title,
+        "author": { "name": n.display_name, "url": n.url },
+    });
+
+    match &n.session {
+        Some(session) => {
+            embed["color"] = json!(OFFLINE_COLOR);
+            embed["fields"] = json!([
+                { "name": "Streamed for", "value": format_duration(session.duration), "inline": true },
+                { "name": "Peak viewers", "value": session.peak_viewers.to_string(), "inline": true },
+                { "name": "Games", "value": or_unknown(&session.games_played.join(", ")), "inline": false },
+            ]);
+        }
+        None => {
+            embed["color"] = json!(LIVE_COLOR);
+            embed["fields"] = json!([
+                { "name": "Game", "value": or_unknown(&stream.game_name), "inline": true },
+                { "name": "Viewers", "value": stream.viewer_count.to_string(), "inline": true },
+            ]);
+            if let Some(thumbnail) = &n.thumbnail_url {
+                // Discord caches images by URL; a new one each time keeps the preview current
+                let cache_buster = chrono::Utc::now().timestamp();
+                embed["image"] = json!({ "url": format!("{}?t={}", thumbnail, cache_buster) });
+            }
+        }
+    }
+    if let Some(avatar_url) = &n.avatar_url {
+        embed["author"]["icon_url"] = json!(avatar_url);
+        embed["thumbnail"] = json!({ "url": avatar_url });
+    }
+    if chrono::DateTime::parse_from_rfc3339(&stream.started_at).is_ok() {
+        embed["timestamp"] = json!(stream.started_at);
+    }
+    embed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::SessionSummary;
+    use axum::extract::{Path, RawQuery, State};
+    use axum::routing::{patch, post};
+    use axum::Router;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// A request the test webhook received.
+    struct Received {
+        method: Method,
+        // The message ID for edits
+        message_id: Option<String>,
+        query: Option<String>,
+        body: Value,
+    }
+
+    /// A Discord webhook that answers with the queued statuses (then success) and
+    /// records requests. Posts get the IDs `msg-1`, `msg-2`, ...
+    #[derive(Default)]
+    struct Webhook {
+        statuses: Mutex<VecDeque<StatusCode>>,
+        received: Mutex<Vec<Received>>,
+    }
+
+    impl Webhook {
+        async fn start(statuses: &[StatusCode]) -> (Arc<Self>, String) {
+            let webhook = Arc::new(Self {
+                statuses: Mutex::new(statuses.iter().copied().collect()),
+                received: Mutex::default(),
+            });
+            let router = Router::new()
+                .route("/api/webhooks/1/token", post(Self::post))
+                .route("/api/webhooks/1/token/messages/{id}", patch(Self::patch))
+                .with_state(webhook.clone());
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!(
+                "http://{}/api/webhooks/1/token",
+                listener.local_addr().unwrap()
+            );
+            tokio::spawn(async move { axum::serve(listener, router).await });
+            (webhook, url)
+        }
+
+        async fn post(
+            State(webhook): State<Arc<Self>>,
+            RawQuery(query): RawQuery,
+            body: String,
+        ) -> (StatusCode, String) {
+            webhook.record(Method::POST, None, query, &body)
+        }
+
+        async fn patch(
+            State(webhook): State<Arc<Self>>,
+            Path(id): Path<String>,
+            body: String,
+        ) -> (StatusCode, String) {
+            webhook.record(Method::PATCH, Some(id.clone()), None, &body)
+        }
+
+        fn record(
+            &self,
+            method: Method,
+            message_id: Option<String>,
+            query: Option<String>,
+            body: &str,
+        ) -> (StatusCode, String) {
+            let mut received = self.received.lock().unwrap();
+            let id = message_id.clone().unwrap_or_else(|| {
+                let posts = received.iter().filter(|r| r.method == Method::POST).count();
+                format!("msg-{}", posts + 1)
+            });
+            received.push(Received {
+                method,
+                message_id,
+                query,
+                body: serde_json::from_str(body).unwrap(),
+            });
+            match self.statuses.lock().unwrap().pop_front() {
+                Some(StatusCode::TOO_MANY_REQUESTS) => (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    json!({ "retry_after": 0.01 }).to_string(),
+                ),
+                Some(status) => (status, String::new()),
+                None => (StatusCode::OK, json!({ "id": id }).to_string()),
+            }
+        }
+
+        /// Waits until `count` requests have come in.
+        async fn wait_for(&self, count: usize) {
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while self.received.lock().unwrap().len() < count {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("webhook didn't receive the expected requests");
+        }
+    }
+
+    fn config(url: &str) -> DiscordSinkConfig {
+        DiscordSinkConfig {
+            webhook_url: url.to_string(),
+            username: None,
+            avatar_url: None,
+            mention_role: None,
+            role_mentions: HashMap::new(),
+        }
+    }
+
+    fn game_change() -> StreamNotification {
+        let mut n = StreamNotification::sample();
+        n.kind = EventKind::Game;
+        n.stream.game_name = "Minecraft".to_string();
+        n.previous_game = Some("Just Chatting".to_string());
+        n
+    }
+
+    fn offline() -> StreamNotification {
+        let mut n = StreamNotification::sample();
+        n.kind = EventKind::Offline;
+        n.summary = "Cooler_User went offline".to_string();
+        n.session = Some(SessionSummary {
+            duration: Duration::from_secs(2 * 60 * 60),
+            peak_viewers: 250,
+            games_played: vec!["Just Chatting".to_string()],
+        });
+        n
+    }
+
+    #[tokio::test]
+    async fn go_live_posts_and_later_events_edit_the_message() {
+        let (webhook, url) = Webhook::start(&[]).await;
+        let sink = DiscordSink::new(&config(&url));
+
+        sink.send(&StreamNotification::sample());
+        sink.send(&game_change());
+        let mut title_change = game_change();
+        title_change.kind = EventKind::Title;
+        title_change.stream.title = "Building".to_string();
+        sink.send(&title_change);
+        webhook.wait_for(3).await;
+
+        let received = webhook.received.lock().unwrap();
+        assert_eq!(received[0].method, Method::POST);
+        assert_eq!(received[0].query.as_deref(), Some("wait=true"));
+        let embed = &received[0].body["embeds"][0];
+        assert_eq!(embed["color"], json!(LIVE_COLOR));
+        assert_eq!(embed["fields"][0]["value"], "Just Chatting");
+
+        for edit in &received[1..] {
+            assert_eq!(edit.method, Method::PATCH);
+            assert_eq!(edit.message_id.as_deref(), Some("msg-1"));
+        }
+        assert_eq!(
+            received[1].body["embeds"][0]["fields"][0]["value"],
+            "Minecraft"
+        );
+        assert_eq!(received[2].body["embeds"][0]["description"], "Building");
+    }
+
+    #[tokio::test]
+    async fn ended_stream_is_greyed_out() {
+        let (webhook, url) = Webhook::start(&[]).await;
+        let sink = DiscordSink::new(&config(&url));
+        let live = StreamNotification::sample();
+
+        sink.send(&live);
+        sink.stream_ended(&live.stream);
+        webhook.wait_for(2).await;
+
+        let received = webhook.received.lock().unwrap();
+        assert_eq!(received[1].method, Method::PATCH);
+        assert_eq!(received[1].message_id.as_deref(), Some("msg-1"));
+        let embed = &received[1].body["embeds"][0];
+        assert_eq!(embed["color"], json!(OFFLINE_COLOR));
+        assert_eq!(embed["footer"]["text"], "Stream ended");
+        assert!(embed.get("image").is_none());
+    }
+
+    #[tokio::test]
+    async fn held_offline_summary_edits_the_ended_message() {
+        let (webhook, url) = Webhook::start(&[]).await;
+        let sink = DiscordSink::new(&config(&url));
+        let live = StreamNotification::sample();
+
+        // Quiet hours held the summary: the stream ends before it is delivered
+        sink.send(&live);
+        sink.stream_ended(&live.stream);
+        sink.send(&offline());
+        // The session is over, so a later ending has nothing to grey out
+        sink.stream_ended(&live.stream);
+        sink.send(&live);
+        webhook.wait_for(4).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let received = webhook.received.lock().unwrap();
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[2].method, Method::PATCH);
+        assert_eq!(received[2].message_id.as_deref(), Some("msg-1"));
+        assert_eq!(received[2].body["embeds"][0]["fields"][1]["value"], "250");
+        assert_eq!(received[3].method, Method::POST);
+    }
+
+    #[tokio::test]
+    async fn mentions_the_streamer_role_only_when_going_live() {
+        let (webhook, url) = Webhook::start(&[]).await;
+        let mut config = config(&url);
+        config.mention_role = Some("7".to_string());
+        config
+            .role_mentions
+            .insert("Cooler_User".to_string(), "42".to_string());
+        let sink = DiscordSink::new(&config);
+
+        sink.send(&StreamNotification::sample());
+        let mut other = StreamNotification::sample();
+        other.stream.user_id = "1".to_string();
+        other.stream.user_login = "other_user".to_string();
+        sink.send(&other);
+        let mut other_offline = offline();
+        other_offline.stream = other.stream.clone();
+        other_offline.stream.user_id = "2".to_string();
+        sink.send(&other_offline);
+        webhook.wait_for(3).await;
+
+        let received = webhook.received.lock().unwrap();
+        assert_eq!(received[0].body["content"], "<@&42>");
+        assert_eq!(received[0].body["allowed_mentions"]["roles"], json!(["42"]));
+        assert_eq!(received[1].body["content"], "<@&7>");
+        assert_eq!(received[2].method, Method::POST);
+        assert!(received[2].body.get("content").is_none());
+        assert_eq!(received[2].body["allowed_mentions"]["roles"], json!([]));
+    }
+
+    #[test]
+    fn role_for_prefers_the_streamer_role() {
+        let mut config = config("http://127.0.0.1/api/webhooks/1/token");
+        let worker = |config: &DiscordSinkConfig| DiscordWorker {
+            client: reqwest::Client::new(),
+            webhook_url: Url::parse(&config.webhook_url).unwrap(),
+            config: config.clone(),
+            messages: HashMap::new(),
+        };
+        assert_eq!(worker(&config).role_for("cooler_user"), None);
+
+        config.mention_role = Some("7".to_string());
+        config
+            .role_mentions
+            .insert("Cooler_User".to_string(), "42".to_string());
+        let worker = worker(&config);
+        assert_eq!(worker.role_for("cooler_user"), Some("42"));
+        assert_eq!(worker.role_for("other_user"), Some("7"));
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limits_and_server_errors() {
+        let (webhook, url) = Webhook::start(&[
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ])
+        .await;
+        let sink = DiscordSink::new(&config(&url));
+
+        sink.send(&StreamNotification::sample());
+        sink.send(&game_change());
+        webhook.wait_for(4).await;
+
+        let received = webhook.received.lock().unwrap();
+        let methods: Vec<&Method> = received.iter().map(|r| &r.method).collect();
+        assert_eq!(
+            methods,
+            vec![&Method::POST, &Method::POST, &Method::POST, &Method::PATCH]
+        );
+        // The third post succeeded and its message is the one edited
+        assert_eq!(received[3].message_id.as_deref(), Some("msg-3"));
+    }
+
+    #[tokio::test]
+    async fn rejected_request_is_not_retried() {
+        let (webhook, url) = Webhook::start(&[StatusCode::BAD_REQUEST]).await;
+        let sink = DiscordSink::new(&config(&url));
+
+        sink.send(&StreamNotification::sample());
+        sink.send(&game_change());
+        webhook.wait_for(2).await;
+
+        // Nothing was posted, so the game change gets a message of its own
+        let received = webhook.received.lock().unwrap();
+        assert_eq!(received[1].method, Method::POST);
+    }
+}
@@ -0,0 +1,874 @@
+// src/eventsub.rs
+
+use futures_util::StreamExt;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::twitch_api::{ApiError, EventSubTransport, TwitchClient};
+
+/// Twitch's production EventSub WebSocket endpoint.
+pub const DEFAULT_WEBSOCKET_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+
+/// Subscription types (and versions) created for every monitored broadcaster.
+const SUBSCRIPTION_TYPES: &[(&str, &str)] = &[
+    ("stream.online", "1"),
+    ("stream.offline", "1"),
+    ("channel.update", "2"),
+];
+
+/// Twitch enables at most this many subscriptions on one WebSocket connection...
+const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 300;
+
+/// ...and allows this many connections per User Access Token.
+pub const MAX_CONNECTIONS: usize = 3;
+
+/// Total cost Twitch allows for one User Access Token's WebSocket subscriptions.
+/// Each subscription to another broadcaster's events costs 1, whatever the connection.
+pub const MAX_TOTAL_COST: usize = 10;
+
+/// Extra time allowed on top of the session's keepalive timeout before reconnecting.
+const KEEPALIVE_GRACE: Duration = Duration::from_secs(5);
+
+/// Used when the welcome message does not specify a keepalive timeout.
+const DEFAULT_KEEPALIVE_TIMEOUT_SECONDS: u64 = 10;
+
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(120);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Error)]
+pub enum EventSubError {
+    #[error("WebSocket error: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+
+    #[error("Failed to parse EventSub message: {0}")]
+    JsonParse(#[from] serde_json::Error),
+
+    #[error("Twitch API error: {0}")]
+    Api(#[from] ApiError),
+
+    #[error("No message received within the keepalive timeout")]
+    KeepaliveTimeout,
+
+    #[error("Connection closed: {0}")]
+    Closed(String),
+
+    #[error("Unexpected message: expected {expected}, got {got}")]
+    UnexpectedMessage { expected: String, got: String },
+}
+
+/// A channel event received through EventSub, independent of the transport.
+#[derive(Debug, Clone)]
+pub enum ChannelEvent {
+    /// `stream.online`: carries no game or title, those must be looked up.
+    Online {
+        user_id: String,
+        user_login: String,
+        user_name: String,
+        stream_id: String,
+        started_at: String,
+    },
+    /// `stream.offline`
+    Offline {
+        user_id: String,
+        user_login: String,
+        user_name: String,
+    },
+    /// `channel.update`: title or category changed, whether live or not.
+    Update {
+        user_id: String,
+        user_login: String,
+        user_name: String,
+        title: String,
+        game_id: String,
+        game_name: String,
+    },
+}
+
+impl ChannelEvent {
+    pub fn user_id(&self) -> &str {
+        match self {
+            ChannelEvent::Online { user_id, .. }
+            | ChannelEvent::Offline { user_id, .. }
+            | ChannelEvent::Update { user_id, .. } => user_id,
+        }
+    }
+
+    pub fn user_login(&self) -> &str {
+        match self {
+            ChannelEvent::Online { user_login, .. }
+            | ChannelEvent::Offline { user_login, .. }
+            | ChannelEvent::Update { user_login, .. } => user_login,
+        }
+    }
+}
+
+/// Metadata shared by every EventSub WebSocket message.
+#[derive(Debug, Deserialize)]
+pub struct MessageMetadata {
+    pub message_id: String,
+    pub message_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebSocketMessage {
+    metadata: MessageMetadata,
+    #[serde(default)]
+    payload: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionPayload {
+    session: Session,
+}
+
+#[derive(Debug, Deserialize)]
+struct Session {
+    id: String,
+    #[serde(default)]
+    keepalive_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    reconnect_url: Option<String>,
+}
+
+/// The subscription an EventSub notification or revocation refers to.
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionInfo {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub subscription_type: String,
+    pub status: String,
+    #[serde(default)]
+    pub condition: serde_json::Value,
+}
+
+/// Payload of a `notification` (or webhook notification) message.
+#[derive(Debug, Deserialize)]
+pub struct NotificationPayload {
+    pub subscription: SubscriptionInfo,
+    #[serde(default)]
+    pub event: serde_json::Value,
+}
+
+/// Payload of a `revocation` message.
+#[derive(Debug, Deserialize)]
+pub struct RevocationPayload {
+    pub subscription: SubscriptionInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamOnlineEvent {
+    id: String,
+    broadcaster_user_id: String,
+    broadcaster_user_login: String,
+    broadcaster_user_name: String,
+    started_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamOfflineEvent {
+    broadcaster_user_id: String,
+    broadcaster_user_login: String,
+    broadcaster_user_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelUpdateEvent {
+    broadcaster_user_id: String,
+    broadcaster_user_login: String,
+    broadcaster_user_name: String,
+    title: String,
+    category_id: String,
+    category_name: String,
+}
+
+/// Converts the `event` object of a notification into a `ChannelEvent`.
+/// Returns `None` for subscription types this app does not use.
+pub fn parse_channel_event(
+    subscription_type: &str,
+    event: serde_json::Value,
+) -> Result<Option<ChannelEvent>, serde_json::Error> {
+    let parsed = match subscription_type {
+        "stream.online" => {
+            let e: StreamOnlineEvent = serde_json::from_value(event)?;
+            ChannelEvent::Online {
+                user_id: e.broadcaster_user_id,
+                user_login: e.broadcaster_user_login,
+                user_name: e.broadcaster_user_name,
+                stream_id: e.id,
+                started_at: e.started_at,
+            }
+        }
+        "stream.offline" => {
+            let e: StreamOfflineEvent = serde_json::from_value(event)?;
+            ChannelEvent::Offline {
+                user_id: e.broadcaster_user_id,
+                user_login: e.broadcaster_user_login,
+                user_name: e.broadcaster_user_name,
+            }
+        }
+        "channel.update" => {
+            let e: ChannelUpdateEvent = serde_json::from_value(event)?;
+            ChannelEvent::Update {
+                user_id: e.broadcaster_user_id,
+                user_login: e.broadcaster_user_login,
+                user_name: e.broadcaster_user_name,
+                title: e.title,
+                game_id: e.category_id,
+                game_name: e.category_name,
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(parsed))
+}
+
+/// Creates the `stream.online`, `stream.offline` and `channel.update` subscriptions
+/// for each broadcaster on the given transport.
+/// A subscription Twitch rejects is logged and skipped, and once it answers 429 (the
+/// transport's cost limit) no more are attempted. Returns the users left without
+/// complete subscriptions, which polling alone covers. Only a missing User Access
+/// Token is an error, since no subscription can succeed without it.
+pub async fn subscribe_all(
+    twitch_client: &TwitchClient,
+    user_ids: &[String],
+    transport: &EventSubTransport,
+) -> Result<Vec<String>, ApiError> {
+    let mut polled_only = Vec::new();
+    for (index, user_id) in user_ids.iter().enumerate() {
+        let mut complete = true;
+        for (subscription_type, version) in SUBSCRIPTION_TYPES {
+            match twitch_client
+                .create_eventsub_subscription(subscription_type, version, user_id, transport)
+                .await
+            {
+                Ok(()) => {}
+                Err(ApiError::MissingUserToken) => return Err(ApiError::MissingUserToken),
+                Err(ApiError::TwitchError { status, .. })
+                    if status == StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    warn!(
+                        "(EventSub) Subscription cost limit reached; {} streamer(s) are only polled.",
+                        user_ids.len() - index
+                    );
+                    polled_only.extend_from_slice(&user_ids[index..]);
+                    return Ok(polled_only);
+                }
+                Err(e) => {
+                    warn!(
+                        user_id = %user_id,
+                        subscription_type,
+                        "(EventSub) Skipping subscription: {}",
+                        e
+                    );
+                    complete = false;
+                }
+            }
+        }
+        if !complete {
+            polled_only.push(user_id.clone());
+        }
+    }
+    info!(
+        "Subscribed to EventSub stream events for {} users",
+        user_ids.len() - polled_only.len()
+    );
+    if !polled_only.is_empty() {
+        warn!(
+            "(EventSub) {} streamer(s) are only polled: {:?}",
+            polled_only.len(),
+            polled_only
+        );
+    }
+    Ok(polled_only)
+}
+
+/// Splits the monitored users over as many WebSocket connections as their
+/// subscriptions need. Users beyond what the token's cost limit (or `MAX_CONNECTIONS`)
+/// can carry are returned separately; polling alone covers them.
+pub fn shard_user_ids(user_ids: &[String]) -> (Vec<Vec<String>>, &[String]) {
+    let per_connection = MAX_SUBSCRIPTIONS_PER_CONNECTION / SUBSCRIPTION_TYPES.len();
+    let affordable = MAX_TOTAL_COST / SUBSCRIPTION_TYPES.len();
+    let capacity = (per_connection * MAX_CONNECTIONS)
+        .min(affordable)
+        .min(user_ids.len());
+    let (covered, uncovered) = user_ids.split_at(capacity);
+    let shards = covered
+        .chunks(per_connection)
+        .map(|chunk| chunk.to_vec())
+        .collect();
+    (shards, uncovered)
+}
+
+/// EventSub WebSocket client. Subscribes to stream events for the monitored users
+/// and forwards them to the monitor over a channel.
+pub struct EventSubClient {
+    websocket_url: String,
+    twitch_client: Arc<TwitchClient>,
+    user_ids: Vec<String>,
+    tx_events: mpsc::Sender<ChannelEvent>,
+}
+
+impl EventSubClient {
+    pub fn new(
+        websocket_url: String,
+        twitch_client: Arc<TwitchClient>,
+        user_ids: Vec<String>,
+        tx_events: mpsc::Sender<ChannelEvent>,
+    ) -> Self {
+        Self {
+            websocket_url,
+            twitch_client,
+            user_ids,
+            tx_events,
+        }
+    }
+
+    /// Runs until the event receiver is dropped, reconnecting with exponential backoff.
+    /// Gives up for good if no User Access Token is configured, leaving polling in charge.
+    pub async fn run(self) {
+        let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
+        loop {
+            match self.run_session(&mut reconnect_delay).await {
+                Ok(()) => {
+                    debug!("(EventSub) Event receiver closed, stopping.");
+                    return;
+                }
+                Err(EventSubError::Api(ApiError::MissingUserToken)) => {
+                    error!("(EventSub) No User Access Token configured. EventSub disabled, relying on polling.");
+                    return;
+                }
+                Err(e) => {
+                    warn!(
+                        "(EventSub) Session ended: {}. Reconnecting in {}s.",
+                        e,
+                        reconnect_delay.as_secs()
+                    );
+                }
+            }
+            tokio::time::sleep(reconnect_delay).await;
+            reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    /// Connects, subscribes and processes messages until the connection fails.
+    /// Returns `Ok(())` only when the event receiver has been dropped.
+    async fn run_session(&self, reconnect_delay: &mut Duration) -> Result<(), EventSubError> {
+        info!("(EventSub) Connecting to {}", self.websocket_url);
+        let (mut socket, session) = connect(&self.websocket_url).await?;
+
+        let transport = EventSubTransport::Websocket {
+            session_id: session.id.clone(),
+        };
+        // Subscribing takes a request per subscription; the socket is read meanwhile
+        // so keepalives (and early events) aren't missed on long lists
+        let subscribing = subscribe_all(&self.twitch_client, &self.user_ids, &transport);
+        tokio::pin!(subscribing);
+        let mut subscribed = false;
+
+        let mut keepalive = keepalive_timeout(&session);
+        loop {
+            let next = tokio::select! {
+                result = &mut subscribing, if !subscribed => {
+                    // Failed subscriptions were logged; those users stay polled only
+                    result?;
+                    subscribed = true;
+                    *reconnect_delay = INITIAL_RECONNECT_DELAY;
+                    continue;
+                }
+                next = timeout(keepalive, socket.next()) => next,
+            };
+            let message = match next {
+                Err(_) => return Err(EventSubError::KeepaliveTimeout),
+                Ok(None) => return Err(EventSubError::Closed("stream ended".to_string())),
+                Ok(Some(message)) => message?,
+            };
+
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(frame) => {
+                    let reason = frame
+                        .map(|f| format!("{} {}", u16::from(f.code), f.reason))
+                        .unwrap_or_else(|| "no close frame".to_string());
+                    return Err(EventSubError::Closed(reason));
+                }
+                // Pings are answered by tungstenite itself
+                _ => continue,
+            };
+
+            let message: WebSocketMessage = serde_json::from_str(text.as_str())?;
+            match message.metadata.message_type.as_str() {
+                "session_keepalive" => trace!("(EventSub) Keepalive received"),
+                "notification" => {
+                    let payload: NotificationPayload = serde_json::from_value(message.payload)?;
+                    let subscription_type = payload.subscription.subscription_type.clone();
+                    match parse_channel_event(&subscription_type, payload.event)? {
+                        Some(event) => {
+                            debug!(
+                                message_id = %message.metadata.message_id,
+                                subscription_type,
+                                user_id = event.user_id(),
+                                login = event.user_login(),
+                                "(EventSub) Event received"
+                            );
+                            if self.tx_events.send(event).await.is_err() {
+                                return Ok(());
+                            }
+                        }
+                        None => debug!(subscription_type, "(EventSub) Ignoring unknown event type"),
+                    }
+                }
+                "session_reconnect" => {
+                    let payload: SessionPayload = serde_json::from_value(message.payload)?;
+                    let reconnect_url = payload.session.reconnect_url.ok_or_else(|| {
+                        EventSubError::Closed("reconnect message without URL".to_string())
+                    })?;
+                    info!("(EventSub) Server requested reconnect, migrating session");
+                    // Subscriptions carry over to the new connection; the old one is
+                    // closed only once the new one has been welcomed.
+                    let (new_socket, new_session) = connect(&reconnect_url).await?;
+                    if let Err(e) = socket.close(None).await {
+                        debug!("(EventSub) Error closing old connection: {}", e);
+                    }
+                    socket = new_socket;
+                    keepalive = keepalive_timeout(&new_session);
+                }
+                "revocation" => {
+                    let payload: RevocationPayload = serde_json::from_value(message.payload)?;
+                    warn!(
+                        id = %payload.subscription.id,
+                        subscription_type = %payload.subscription.subscription_type,
+                        status = %payload.subscription.status,
+                        condition = %payload.subscription.condition,
+                        "(EventSub) Subscription revoked by Twitch"
+                    );
+                }
+                other => debug!(message_type = other, "(EventSub) Ignoring message"),
+            }
+        }
+    }
+}
+
+/// Opens a WebSocket connection and waits for its `session_welcome` message.
+async fn connect(url: &str) -> Result<(Socket, Session), EventSubError> {
+    let (mut socket, _response) = connect_async(url).await?;
+
+    // Twitch sends the welcome immediately; use the default keepalive until we know better
+    let wait = Duration::from_secs(DEFAULT_KEEPALIVE_TIMEOUT_SECONDS) + KEEPALIVE_GRACE;
+    loop {
+        let message = match timeout(wait, socket.next()).await {
+            Err(_) => return Err(EventSubError::KeepaliveTimeout),
+            Ok(None) => return Err(EventSubError::Closed("closed before welcome".to_string())),
+            Ok(Some(message)) => message?,
+        };
+        let Message::Text(text) = message else {
+            continue;
+        };
+
+        let message: WebSocketMessage = serde_json::from_str(text.as_str())?;
+        if message.metadata.message_type != "session_welcome" {
+            return Err(EventSubError::UnexpectedMessage {
+                expected: "session_welcome".to_string(),
+                got: message.metadata.message_type,
+            });
+        }
+        let payload: SessionPayload = serde_json::from_value(message.payload)?;
+        info!(session_id = %payload.session.id, "(EventSub) Session established");
+        return Ok((socket, payload.session));
+    }
+}
+
+fn keepalive_timeout(session: &Session) -> Duration {
+    Duration::from_secs(
+        session
+            .keepalive_timeout_seconds
+            .unwrap_or(DEFAULT_KEEPALIVE_TIMEOUT_SECONDS),
+    ) + KEEPALIVE_GRACE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::StatusCode as HttpStatus;
+    use axum::routing::post;
+    use axum::Router;
+    use futures_util::SinkExt;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+
+    type ServerSocket = WebSocketStream<TcpStream>;
+
+    /// Broadcaster IDs of the subscription requests a mock API received.
+    type Requests = Arc<Mutex<Vec<String>>>;
+
+    /// Serves a mock subscriptions endpoint that answers `reject(broadcaster_id)` when
+    /// it returns a status, and 202 Accepted otherwise.
+    async fn start_api(reject: fn(&str) -> Option<HttpStatus>) -> (String, Requests) {
+        let requests = Requests::default();
+        let handler = move |State(requests): State<Requests>, body: String| async move {
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            let broadcaster_id = body["condition"]["broadcaster_user_id"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            requests.lock().unwrap().push(broadcaster_id.clone());
+            let (status, response) = match reject(&broadcaster_id) {
+                Some(status) => (status, serde_json::json!({ "message": "rejected" })),
+                None => (
+                    HttpStatus::ACCEPTED,
+                    serde_json::json!({ "data": [{
+                        "id": format!("sub-{}", broadcaster_id),
+                        "status": "enabled",
+                        "type": body["type"],
+                        "version": body["version"]
+                    }] }),
+                ),
+            };
+            (status, response.to_string())
+        };
+        let router = Router::new()
+            .route("/eventsub/subscriptions", post(handler))
+            .with_state(requests.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/eventsub/subscriptions",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, requests)
+    }
+
+    fn client(subscriptions_url: String) -> TwitchClient {
+        TwitchClient::new("client-id".to_string(), "secret".to_string())
+            .unwrap()
+            .with_user_access_token(Some("user-token".to_string()))
+            .with_eventsub_subscriptions_url(subscriptions_url)
+    }
+
+    fn user_ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn websocket_transport() -> EventSubTransport {
+        EventSubTransport::Websocket {
+            session_id: "session-1".to_string(),
+        }
+    }
+
+    /// Runs an `EventSubClient` for `ids` against a local WebSocket server.
+    async fn start_session(
+        ids: &[&str],
+        reject: fn(&str) -> Option<HttpStatus>,
+    ) -> (TcpListener, Requests, mpsc::Receiver<ChannelEvent>) {
+        let (api_url, requests) = start_api(reject).await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let websocket_url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let (tx_events, rx_events) = mpsc::channel(8);
+        let client = EventSubClient::new(
+            websocket_url,
+            Arc::new(client(api_url)),
+            user_ids(ids),
+            tx_events,
+        );
+        tokio::spawn(client.run());
+        (listener, requests, rx_events)
+    }
+
+    async fn accept(listener: &TcpListener) -> ServerSocket {
+        let (stream, _) = timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .expect("client did not connect")
+            .unwrap();
+        tokio_tungstenite::accept_async(stream).await.unwrap()
+    }
+
+    async fn send(socket: &mut ServerSocket, message_type: &str, payload: serde_json::Value) {
+        let message = serde_json::json!({
+            "metadata": { "message_id": format!("{}-id", message_type), "message_type": message_type },
+            "payload": payload
+        });
+        socket
+            .send(Message::Text(message.to_string().into()))
+            .await
+            .unwrap();
+    }
+
+    async fn welcome(socket: &mut ServerSocket, keepalive_timeout_seconds: u64) {
+        let session = serde_json::json!({ "session": {
+            "id": "session-1",
+            "status": "connected",
+            "keepalive_timeout_seconds": keepalive_timeout_seconds
+        } });
+        send(socket, "session_welcome", session).await;
+    }
+
+    fn offline_notification(user_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "subscription": {
+                "id": "sub-1",
+                "type": "stream.offline",
+                "status": "enabled",
+                "condition": { "broadcaster_user_id": user_id }
+            },
+            "event": {
+                "broadcaster_user_id": user_id,
+                "broadcaster_user_login": "cooler_user",
+                "broadcaster_user_name": "Cooler_User"
+            }
+        })
+    }
+
+    async fn next_event(rx_events: &mut mpsc::Receiver<ChannelEvent>) -> ChannelEvent {
+        timeout(Duration::from_secs(5), rx_events.recv())
+            .await
+            .expect("no event forwarded")
+            .unwrap()
+    }
+
+    /// Waits until the mock API has seen `count` subscription requests.
+    async fn wait_for_requests(requests: &Requests, count: usize) {
+        for _ in 0..100 {
+            if requests.lock().unwrap().len() >= count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("expected {} subscription requests", count);
+    }
+
+    async fn assert_no_reconnect(listener: &TcpListener) {
+        assert!(
+            timeout(Duration::from_millis(300), listener.accept())
+                .await
+                .is_err(),
+            "client reconnected"
+        );
+    }
+
+    #[test]
+    fn parse_channel_event_converts_each_subscription_type() {
+        let online = serde_json::json!({
+            "id": "9001",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "type": "live",
+            "started_at": "2020-10-11T10:11:12.123Z"
+        });
+        match parse_channel_event("stream.online", online).unwrap() {
+            Some(ChannelEvent::Online {
+                user_id,
+                stream_id,
+                started_at,
+                ..
+            }) => {
+                assert_eq!(user_id, "1337");
+                assert_eq!(stream_id, "9001");
+                assert_eq!(started_at, "2020-10-11T10:11:12.123Z");
+            }
+            other => panic!("expected an online event, got {:?}", other),
+        }
+
+        let offline = offline_notification("1337")["event"].clone();
+        match parse_channel_event("stream.offline", offline).unwrap() {
+            Some(ChannelEvent::Offline {
+                user_id,
+                user_login,
+                ..
+            }) => {
+                assert_eq!(user_id, "1337");
+                assert_eq!(user_login, "cooler_user");
+            }
+            other => panic!("expected an offline event, got {:?}", other),
+        }
+
+        let update = serde_json::json!({
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "title": "Best Stream Ever",
+            "language": "en",
+            "category_id": "12453",
+            "category_name": "Grand Theft Auto",
+            "content_classification_labels": []
+        });
+        match parse_channel_event("channel.update", update).unwrap() {
+            Some(ChannelEvent::Update {
+                title,
+                game_id,
+                game_name,
+                ..
+            }) => {
+                assert_eq!(title, "Best Stream Ever");
+                assert_eq!(game_id, "12453");
+                assert_eq!(game_name, "Grand Theft Auto");
+            }
+            other => panic!("expected an update event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_channel_event_ignores_unknown_types_and_rejects_bad_events() {
+        let event = serde_json::json!({ "broadcaster_user_id": "1337" });
+        assert!(parse_channel_event("channel.follow", event.clone())
+            .unwrap()
+            .is_none());
+        assert!(parse_channel_event("stream.offline", event).is_err());
+    }
+
+    #[test]
+    fn shard_user_ids_stays_within_the_cost_limit() {
+        let ids: Vec<String> = (0..10).map(|id| id.to_string()).collect();
+        let (shards, uncovered) = shard_user_ids(&ids);
+
+        let covered: usize = shards.iter().map(Vec::len).sum();
+        assert!(covered * SUBSCRIPTION_TYPES.len() <= MAX_TOTAL_COST);
+        assert_eq!(covered + uncovered.len(), ids.len());
+        assert_eq!(uncovered[0], ids[covered]);
+    }
+
+    #[tokio::test]
+    async fn subscribe_all_skips_rejected_users() {
+        let (url, requests) = start_api(|id| (id == "2").then_some(HttpStatus::FORBIDDEN)).await;
+
+        let polled_only = subscribe_all(
+            &client(url),
+            &user_ids(&["1", "2", "3"]),
+            &websocket_transport(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(polled_only, vec!["2".to_string()]);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3 * SUBSCRIPTION_TYPES.len());
+        assert!(requests.iter().any(|id| id == "3"));
+    }
+
+    #[tokio::test]
+    async fn subscribe_all_stops_at_the_cost_limit() {
+        let (url, requests) =
+            start_api(|id| (id == "2").then_some(HttpStatus::TOO_MANY_REQUESTS)).await;
+
+        let polled_only = subscribe_all(
+            &client(url),
+            &user_ids(&["1", "2", "3"]),
+            &websocket_transport(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(polled_only, user_ids(&["2", "3"]));
+        assert!(!requests.lock().unwrap().iter().any(|id| id == "3"));
+    }
+
+    #[tokio::test]
+    async fn subscribe_all_requires_a_user_token_for_websockets() {
+        let client = TwitchClient::new("client-id".to_string(), "secret".to_string()).unwrap();
+
+        let result = subscribe_all(&client, &user_ids(&["1"]), &websocket_transport()).await;
+
+        assert!(matches!(result, Err(ApiError::MissingUserToken)));
+    }
+
+    #[tokio::test]
+    async fn session_survives_failed_subscriptions_and_forwards_events() {
+        let (listener, requests, mut rx_events) = start_session(&["1", "2"], |id| {
+            (id == "2").then_some(HttpStatus::FORBIDDEN)
+        })
+        .await;
+        let mut socket = accept(&listener).await;
+        welcome(&mut socket, 10).await;
+
+        wait_for_requests(&requests, 2 * SUBSCRIPTION_TYPES.len()).await;
+        send(&mut socket, "session_keepalive", serde_json::json!({})).await;
+        send(&mut socket, "notification", offline_notification("1")).await;
+
+        let event = next_event(&mut rx_events).await;
+        assert!(matches!(event, ChannelEvent::Offline { ref user_id, .. } if user_id == "1"));
+        assert_no_reconnect(&listener).await;
+    }
+
+    #[tokio::test]
+    async fn session_reconnects_after_the_keepalive_timeout() {
+        let (listener, _requests, _rx_events) = start_session(&[], |_| None).await;
+        let mut socket = accept(&listener).await;
+        welcome(&mut socket, 0).await;
+
+        // Nothing is sent: the client gives up after the grace period and reconnects
+        let (stream, _) = timeout(KEEPALIVE_GRACE * 2, listener.accept())
+            .await
+            .expect("client did not reconnect")
+            .unwrap();
+        drop(stream);
+    }
+
+    #[tokio::test]
+    async fn session_reconnect_migrates_without_resubscribing() {
+        let (listener, requests, mut rx_events) = start_session(&["1"], |_| None).await;
+        let mut old = accept(&listener).await;
+        welcome(&mut old, 10).await;
+        wait_for_requests(&requests, SUBSCRIPTION_TYPES.len()).await;
+
+        let reconnect_url = format!("ws://{}/ws?reconnect", listener.local_addr().unwrap());
+        let session = serde_json::json!({ "session": {
+            "id": "session-1",
+            "status": "reconnecting",
+            "reconnect_url": reconnect_url
+        } });
+        send(&mut old, "session_reconnect", session).await;
+        let mut new = accept(&listener).await;
+        welcome(&mut new, 10).await;
+
+        // The old connection is closed once the new one is welcomed
+        let closed = timeout(Duration::from_secs(5), async {
+            while let Some(Ok(message)) = old.next().await {
+                if message.is_close() {
+                    return;
+                }
+            }
+        })
+        .await;
+        assert!(closed.is_ok(), "old connection was not closed");
+
+        send(&mut new, "notification", offline_notification("1")).await;
+        assert!(matches!(
+            next_event(&mut rx_events).await,
+            ChannelEvent::Offline { .. }
+        ));
+        assert_eq!(requests.lock().unwrap().len(), SUBSCRIPTION_TYPES.len());
+    }
+
+    #[tokio::test]
+    async fn revocation_keeps_the_session_up() {
+        let (listener, _requests, mut rx_events) = start_session(&[], |_| None).await;
+        let mut socket = accept(&listener).await;
+        welcome(&mut socket, 10).await;
+
+        let revocation = serde_json::json!({ "subscription": {
+            "id": "sub-1",
+            "type": "stream.online",
+            "status": "authorization_revoked",
+            "condition": { "broadcaster_user_id": "1" }
+        } });
+        send(&mut socket, "revocation", revocation).await;
+        send(&mut socket, "notification", offline_notification("1")).await;
+
+        assert!(matches!(
+            next_event(&mut rx_events).await,
+            ChannelEvent::Offline { .. }
+        ));
+        assert_no_reconnect(&listener).await;
+    }
+}
@@ -0,0 +1,537 @@
+// src/eventsub_webhook.rs
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::SendTimeoutError;
+use tracing::{debug, error, info, warn};
+
+use crate::eventsub::{parse_channel_event, ChannelEvent, NotificationPayload, RevocationPayload};
+
+const HEADER_MESSAGE_ID: &str = "twitch-eventsub-message-id";
+const HEADER_MESSAGE_TIMESTAMP: &str = "twitch-eventsub-message-timestamp";
+const HEADER_MESSAGE_SIGNATURE: &str = "twitch-eventsub-message-signature";
+const HEADER_MESSAGE_TYPE: &str = "twitch-eventsub-message-type";
+
+/// Messages older than this are rejected to prevent replay attacks, as Twitch recommends.
+const MAX_MESSAGE_AGE_SECONDS: i64 = 10 * 60;
+
+/// Number of recent message IDs remembered for de-duplication.
+const SEEN_MESSAGE_CAPACITY: usize = 1000;
+
+/// How long a notification may wait for room in the event channel. Twitch expects
+/// a response within a few seconds and retries the message if it gets an error.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Twitch requires webhook secrets of 10 to 100 ASCII characters.
+pub const SECRET_LENGTH: std::ops::RangeInclusive<usize> = 10..=100;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 of `id + timestamp + body`, which Twitch sends hex encoded as
+/// `sha256=<hex>` in the `Twitch-Eventsub-Message-Signature` header.
+fn message_mac(secret: &str, message_id: &str, timestamp: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(message_id.as_bytes());
+    mac.update(timestamp.as_bytes());
+    mac.update(body);
+    mac
+}
+
+/// Checks a signature header against the message in constant time.
+fn verify_signature(
+    secret: &str,
+    message_id: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+) -> bool {
+    let Some(expected) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex_sig| hex::decode(hex_sig).ok())
+    else {
+        return false;
+    };
+
+    message_mac(secret, message_id, timestamp, body)
+        .verify_slice(&expected)
+        .is_ok()
+}
+
+/// True if the RFC 3339 timestamp is within `MAX_MESSAGE_AGE_SECONDS` of now.
+fn is_fresh(timestamp: &str) -> bool {
+    match chrono::DateTime::parse_from_rfc3339(timestamp) {
+        Ok(sent_at) => {
+            let age = chrono::Utc::now().signed_duration_since(sent_at);
+            age.num_seconds().abs() <= MAX_MESSAGE_AGE_SECONDS
+        }
+        Err(_) => false,
+    }
+}
+
+/// Bounded set of recently seen message IDs; Twitch may deliver a message more than once.
+#[derive(Debug, Default)]
+struct RecentMessageIds {
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl RecentMessageIds {
+    /// Records the ID, returning false if it had already been seen.
+    fn insert(&mut self, id: &str) -> bool {
+        if self.ids.contains(id) {
+            return false;
+        }
+        if self.order.len() >= SEEN_MESSAGE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.ids.insert(id.to_string());
+        self.order.push_back(id.to_string());
+        true
+    }
+
+    /// Forgets the ID, so that Twitch's retry of a message that failed is handled.
+    fn remove(&mut self, id: &str) {
+        if self.ids.remove(id) {
+            self.order.retain(|seen| seen != id);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VerificationPayload {
+    challenge: String,
+}
+
+#[derive(Clone)]
+struct WebhookState {
+    secret: Arc<String>,
+    seen: Arc<Mutex<RecentMessageIds>>,
+    tx_events: mpsc::Sender<ChannelEvent>,
+}
+
+/// Builds the router that receives EventSub webhook deliveries on `path`.
+pub fn router(path: &str, secret: String, tx_events: mpsc::Sender<ChannelEvent>) -> Router {
+    let state = WebhookState {
+        secret: Arc::new(secret),
+        seen: Arc::new(Mutex::new(RecentMessageIds::default())),
+        tx_events,
+    };
+    Router::new()
+        .route(path, post(handle_message))
+        .with_state(state)
+}
+
+/// Serves the webhook receiver on an already bound listener until the process exits.
+pub async fn serve(listener: TcpListener, router: Router) {
+    match listener.local_addr() {
+        Ok(addr) => info!("(EventSub Webhook) Listening on {}", addr),
+        Err(e) => debug!("(EventSub Webhook) Listening (address unknown: {})", e),
+    }
+    if let Err(e) = axum::serve(listener, router).await {
+        error!("(EventSub Webhook) Server stopped: {}", e);
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+async fn handle_message(
+    State(state): State<WebhookState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let (Some(message_id), Some(timestamp), Some(signature), Some(message_type)) = (
+        header_str(&headers, HEADER_MESSAGE_ID),
+        header_str(&headers, HEADER_MESSAGE_TIMESTAMP),
+        header_str(&headers, HEADER_MESSAGE_SIGNATURE),
+        header_str(&headers, HEADER_MESSAGE_TYPE),
+    ) else {
+        warn!("(EventSub Webhook) Request without EventSub headers rejected");
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    if !verify_signature(&state.secret, message_id, timestamp, &body, signature) {
+        warn!(
+            message_id,
+            "(EventSub Webhook) Invalid signature, message rejected"
+        );
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !is_fresh(timestamp) {
+        warn!(
+            message_id,
+            timestamp, "(EventSub Webhook) Stale message rejected"
+        );
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let first_delivery = state
+        .seen
+        .lock()
+        .expect("message ID mutex poisoned")
+        .insert(message_id);
+    if !first_delivery {
+        debug!(message_id, "(EventSub Webhook) Duplicate message ignored");
+        return StatusCode::NO_CONTENT.into_response();
+    }
+
+    let response = handle_new_message(&state, message_id, message_type, &body).await;
+    // Only handled messages count as seen; Twitch retries the others
+    if !response.status().is_success() {
+        state
+            .seen
+            .lock()
+            .expect("message ID mutex poisoned")
+            .remove(message_id);
+    }
+    response
+}
+
+/// Handles a verified message delivered for the first time.
+async fn handle_new_message(
+    state: &WebhookState,
+    message_id: &str,
+    message_type: &str,
+    body: &[u8],
+) -> Response {
+    match message_type {
+        "webhook_callback_verification" => {
+            match serde_json::from_slice::<VerificationPayload>(body) {
+                Ok(payload) => {
+                    info!("(EventSub Webhook) Answering subscription verification challenge");
+                    (
+                        StatusCode::OK,
+                        [(header::CONTENT_TYPE, "text/plain")],
+                        payload.challenge,
+                    )
+                        .into_response()
+                }
+                Err(e) => {
+                    warn!("(EventSub Webhook) Malformed verification request: {}", e);
+                    StatusCode::BAD_REQUEST.into_response()
+                }
+            }
+        }
+        "notification" => {
+            let event = serde_json::from_slice::<NotificationPayload>(body).and_then(|payload| {
+                parse_channel_event(&payload.subscription.subscription_type, payload.event)
+            });
+            match event {
+                Ok(Some(event)) => {
+                    debug!(
+                        message_id,
+                        user_id = event.user_id(),
+                        login = event.user_login(),
+                        "(EventSub Webhook) Event received"
+                    );
+                    match state.tx_events.send_timeout(event, FORWARD_TIMEOUT).await {
+                        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+                        Err(SendTimeoutError::Timeout(_)) => {
+                            warn!(
+                                message_id,
+                                "(EventSub Webhook) Monitor is busy, Twitch will retry the event"
+                            );
+                            StatusCode::SERVICE_UNAVAILABLE.into_response()
+                        }
+                        Err(SendTimeoutError::Closed(_)) => {
+                            warn!("(EventSub Webhook) Monitor is gone, dropping event");
+                            StatusCode::SERVICE_UNAVAILABLE.into_response()
+                        }
+                    }
+                }
+                Ok(None) => StatusCode::NO_CONTENT.into_response(),
+                Err(e) => {
+                    warn!("(EventSub Webhook) Malformed notification: {}", e);
+                    StatusCode::BAD_REQUEST.into_response()
+                }
+            }
+        }
+        "revocation" => {
+            match serde_json::from_slice::<RevocationPayload>(body) {
+                Ok(payload) => warn!(
+                    id = %payload.subscription.id,
+                    subscription_type = %payload.subscription.subscription_type,
+                    status = %payload.subscription.status,
+                    "(EventSub Webhook) Subscription revoked by Twitch"
+                ),
+                Err(e) => warn!("(EventSub Webhook) Malformed revocation: {}", e),
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        other => {
+            debug!(message_type = other, "(EventSub Webhook) Ignoring message");
+            StatusCode::NO_CONTENT.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "s3cr3t-for-tests";
+    const PATH: &str = "/eventsub";
+
+    fn sign(message_id: &str, timestamp: &str, body: &str) -> String {
+        let mac = message_mac(SECRET, message_id, timestamp, body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn now() -> String {
+        chrono::Utc::now().to_rfc3339()
+    }
+
+    /// Serves the router on a free local port, returning its URL and the event receiver.
+    async fn start_receiver() -> (String, mpsc::Receiver<ChannelEvent>) {
+        start_receiver_with_capacity(8).await
+    }
+
+    async fn start_receiver_with_capacity(
+        capacity: usize,
+    ) -> (String, mpsc::Receiver<ChannelEvent>) {
+        let (tx_events, rx_events) = mpsc::channel(capacity);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), PATH);
+        tokio::spawn(serve(listener, router(PATH, SECRET.to_string(), tx_events)));
+        (url, rx_events)
+    }
+
+    async fn deliver(
+        url: &str,
+        message_id: &str,
+        timestamp: &str,
+        message_type: &str,
+        body: &str,
+        signature: &str,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(url)
+            .header(HEADER_MESSAGE_ID, message_id)
+            .header(HEADER_MESSAGE_TIMESTAMP, timestamp)
+            .header(HEADER_MESSAGE_SIGNATURE, signature)
+            .header(HEADER_MESSAGE_TYPE, message_type)
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap()
+    }
+
+    fn offline_notification() -> String {
+        serde_json::json!({
+            "subscription": {
+                "id": "sub-1",
+                "type": "stream.offline",
+                "status": "enabled",
+                "condition": { "broadcaster_user_id": "1337" }
+            },
+            "event": {
+                "broadcaster_user_id": "1337",
+                "broadcaster_user_login": "cooler_user",
+                "broadcaster_user_name": "Cooler_User"
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn verify_signature_accepts_valid_and_rejects_tampered() {
+        let timestamp = now();
+        let body = offline_notification();
+        let signature = sign("msg-1", &timestamp, &body);
+
+        assert!(verify_signature(
+            SECRET,
+            "msg-1",
+            &timestamp,
+            body.as_bytes(),
+            &signature
+        ));
+        let tampered = body.replace("cooler_user", "other_user");
+        assert!(!verify_signature(
+            SECRET,
+            "msg-1",
+            &timestamp,
+            tampered.as_bytes(),
+            &signature
+        ));
+        assert!(!verify_signature(
+            "wrong-secret",
+            "msg-1",
+            &timestamp,
+            body.as_bytes(),
+            &signature
+        ));
+        assert!(!verify_signature(
+            SECRET,
+            "msg-1",
+            &timestamp,
+            body.as_bytes(),
+            "sha256=zz"
+        ));
+    }
+
+    #[test]
+    fn is_fresh_rejects_old_and_malformed_timestamps() {
+        assert!(is_fresh(&now()));
+        let stale = chrono::Utc::now() - chrono::Duration::seconds(MAX_MESSAGE_AGE_SECONDS + 60);
+        assert!(!is_fresh(&stale.to_rfc3339()));
+        assert!(!is_fresh("yesterday"));
+    }
+
+    #[tokio::test]
+    async fn valid_notification_is_forwarded() {
+        let (url, mut rx_events) = start_receiver().await;
+        let timestamp = now();
+        let body = offline_notification();
+        let signature = sign("msg-1", &timestamp, &body);
+
+        let response = deliver(&url, "msg-1", &timestamp, "notification", &body, &signature).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        match rx_events.try_recv() {
+            Ok(ChannelEvent::Offline { user_login, .. }) => assert_eq!(user_login, "cooler_user"),
+            other => panic!("expected an offline event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn tampered_body_is_rejected() {
+        let (url, mut rx_events) = start_receiver().await;
+        let timestamp = now();
+        let body = offline_notification();
+        let signature = sign("msg-1", &timestamp, &body);
+        let tampered = body.replace("1337", "4242");
+
+        let response = deliver(
+            &url,
+            "msg-1",
+            &timestamp,
+            "notification",
+            &tampered,
+            &signature,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(rx_events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn stale_message_is_rejected() {
+        let (url, mut rx_events) = start_receiver().await;
+        let timestamp = (chrono::Utc::now() - chrono::Duration::minutes(11)).to_rfc3339();
+        let body = offline_notification();
+        let signature = sign("msg-1", &timestamp, &body);
+
+        let response = deliver(&url, "msg-1", &timestamp, "notification", &body, &signature).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(rx_events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn duplicate_message_is_delivered_once() {
+        let (url, mut rx_events) = start_receiver().await;
+        let timestamp = now();
+        let body = offline_notification();
+        let signature = sign("msg-1", &timestamp, &body);
+
+        for _ in 0..2 {
+            let response =
+                deliver(&url, "msg-1", &timestamp, "notification", &body, &signature).await;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        }
+        assert!(rx_events.try_recv().is_ok());
+        assert!(rx_events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_handled_when_retried() {
+        let (url, mut rx_events) = start_receiver_with_capacity(1).await;
+        let timestamp = now();
+        let body = offline_notification();
+
+        // The monitor hasn't taken the first event yet, so the second can't be forwarded
+        let signature = sign("msg-1", &timestamp, &body);
+        let response = deliver(&url, "msg-1", &timestamp, "notification", &body, &signature).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let signature = sign("msg-2", &timestamp, &body);
+        let response = deliver(&url, "msg-2", &timestamp, "notification", &body, &signature).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        assert!(rx_events.try_recv().is_ok());
+        let response = deliver(&url, "msg-2", &timestamp, "notification", &body, &signature).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(rx_events.try_recv().is_ok());
+        assert!(rx_events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn malformed_notification_is_not_remembered() {
+        let (url, mut rx_events) = start_receiver().await;
+        let timestamp = now();
+        let body = serde_json::json!({ "subscription": { "id": "sub-1" } }).to_string();
+        let signature = sign("msg-1", &timestamp, &body);
+
+        for _ in 0..2 {
+            let response =
+                deliver(&url, "msg-1", &timestamp, "notification", &body, &signature).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(rx_events.try_recv().is_err());
+    }
+
+    #[test]
+    fn recent_message_ids_forget_removed_ids() {
+        let mut seen = RecentMessageIds::default();
+        assert!(seen.insert("msg-1"));
+        assert!(!seen.insert("msg-1"));
+        seen.remove("msg-1");
+        assert!(seen.insert("msg-1"));
+        assert_eq!(seen.order.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn callback_verification_echoes_challenge() {
+        let (url, _rx_events) = start_receiver().await;
+        let timestamp = now();
+        let body = serde_json::json!({
+            "challenge": "pogchamp-kappa-360noscope-vohiyo",
+            "subscription": { "id": "sub-1", "type": "stream.online", "status": "webhook_callback_verification_pending" }
+        })
+        .to_string();
+        let signature = sign("msg-1", &timestamp, &body);
+
+        let response = deliver(
+            &url,
+            "msg-1",
+            &timestamp,
+            "webhook_callback_verification",
+            &body,
+            &signature,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE].to_str().unwrap(),
+            "text/plain"
+        );
+        assert_eq!(
+            response.text().await.unwrap(),
+            "pogchamp-kappa-360noscope-vohiyo"
+        );
+    }
+}
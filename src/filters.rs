@@ -0,0 +1,319 @@
+// src/filters.rs
+
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+/// Title filter as written in the config file.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TitleFilterConfig {
+    // Plain substrings, matched case-insensitively
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    // Regular expressions, matched case-insensitively
+    #[serde(default)]
+    pub regexes: Vec<String>,
+    #[serde(default)]
+    pub exclude_keywords: Vec<String>,
+    #[serde(default)]
+    pub exclude_regexes: Vec<String>,
+    // Notify when a live streamer's title changes to a matching one
+    #[serde(default = "default_true")]
+    pub notify_on_change: bool,
+    // Only send go-live notifications if the title matches
+    #[serde(default)]
+    pub require_match_for_live: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Compiled title filter. Regexes are compiled when the config is loaded,
+/// so an invalid pattern is reported at startup.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "TitleFilterConfig")]
+pub struct TitleFilter {
+    keywords: Vec<String>,
+    regexes: Vec<Regex>,
+    exclude_keywords: Vec<String>,
+    exclude_regexes: Vec<Regex>,
+    pub notify_on_change: bool,
+    pub require_match_for_live: bool,
+}
+
+impl Default for TitleFilter {
+    fn default() -> Self {
+        Self {
+            keywords: Vec::new(),
+            regexes: Vec::new(),
+            exclude_keywords: Vec::new(),
+            exclude_regexes: Vec::new(),
+            notify_on_change: true,
+            require_match_for_live: false,
+        }
+    }
+}
+
+impl TryFrom<TitleFilterConfig> for TitleFilter {
+    type Error = String;
+
+    fn try_from(config: TitleFilterConfig) -> Result<Self, Self::Error> {
+        Ok(Self {
+            keywords: lowercase_all(&config.keywords),
+            regexes: compile_all(&config.regexes)?,
+            exclude_keywords: lowercase_all(&config.exclude_keywords),
+            exclude_regexes: compile_all(&config.exclude_regexes)?,
+            notify_on_change: config.notify_on_change,
+            require_match_for_live: config.require_match_for_live,
+        })
+    }
+}
+
+fn lowercase_all(keywords: &[String]) -> Vec<String> {
+    keywords.iter().map(|k| k.to_lowercase()).collect()
+}
+
+fn compile_all(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| format!("invalid title regex '{}': {}", pattern, e))
+        })
+        .collect()
+}
+
+impl TitleFilter {
+    fn has_include_patterns(&self) -> bool {
+        !self.keywords.is_empty() || !self.regexes.is_empty()
+    }
+
+    fn is_excluded(&self, title_lower: &str, title: &str) -> bool {
+        self.exclude_keywords
+            .iter()
+            .any(|k| title_lower.contains(k.as_str()))
+            || self.exclude_regexes.iter().any(|r| r.is_match(title))
+    }
+
+    fn is_included(&self, title_lower: &str, title: &str) -> bool {
+        self.keywords
+            .iter()
+            .any(|k| title_lower.contains(k.as_str()))
+            || self.regexes.iter().any(|r| r.is_match(title))
+    }
+
+    /// True if the title matches an include pattern and no exclude pattern.
+    /// A filter without include patterns matches nothing.
+    pub fn matches(&self, title: &str) -> bool {
+        let title_lower = title.to_lowercase();
+        self.is_included(&title_lower, title) && !self.is_excluded(&title_lower, title)
+    }
+
+    /// Whether a title change to `title` should be notified.
+    pub fn allows_title_change(&self, title: &str) -> bool {
+        self.notify_on_change && self.matches(title)
+    }
+
+    /// Whether a go-live with `title` should be notified. Every go-live passes unless
+    /// `require_match_for_live` is set; then excludes block it and includes (if any) must match.
+    pub fn allows_live(&self, title: &str) -> bool {
+        if !self.require_match_for_live {
+            return true;
+        }
+        let title_lower = title.to_lowercase();
+        if self.is_excluded(&title_lower, title) {
+            return false;
+        }
+        !self.has_include_patterns() || self.is_included(&title_lower, title)
+    }
+}
+
+/// How the game list of a `GameFilter` is applied.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GameFilterMode {
+    /// Every game is allowed.
+    #[default]
+    Off,
+    /// Only the listed games are allowed.
+    Allowlist,
+    /// Every game except the listed ones is allowed.
+    Blocklist,
+}
+
+/// Game filter for go-live and game-change notifications.
+/// Entries match a stream's `game_id` exactly or its `game_name` case-insensitively.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GameFilter {
+    #[serde(default)]
+    pub mode: GameFilterMode,
+    #[serde(default)]
+    pub games: Vec<String>,
+    // Notify when a live streamer switches from a filtered-out game into an allowed one
+    #[serde(default = "default_true")]
+    pub notify_on_switch_into: bool,
+}
+
+impl Default for GameFilter {
+    fn default() -> Self {
+        Self {
+            mode: GameFilterMode::Off,
+            games: Vec::new(),
+            notify_on_switch_into: true,
+        }
+    }
+}
+
+impl GameFilter {
+    fn is_listed(&self, game_id: &str, game_name: &str) -> bool {
+        // Game names aren't ASCII-only ("Pokémon", "原神"), so compare full lowercase forms
+        let name_lower = game_name.to_lowercase();
+        self.games
+            .iter()
+            .any(|g| g == game_id || g.to_lowercase() == name_lower)
+    }
+
+    /// True if notifications for this game are allowed.
+    pub fn allows(&self, game_id: &str, game_name: &str) -> bool {
+        match self.mode {
+            GameFilterMode::Off => true,
+            GameFilterMode::Allowlist => self.is_listed(game_id, game_name),
+            GameFilterMode::Blocklist => !self.is_listed(game_id, game_name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn title_filter(config: serde_json::Value) -> TitleFilter {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn keywords_match_case_insensitively() {
+        let filter = title_filter(json!({ "keywords": ["Speedrun", "ÉVÉNEMENT"] }));
+        assert!(filter.matches("any% SPEEDRUN attempts"));
+        assert!(filter.matches("Grand événement ce soir"));
+        assert!(!filter.matches("Just chatting"));
+    }
+
+    #[test]
+    fn regexes_match_case_insensitively() {
+        let filter = title_filter(json!({ "regexes": [r"\bday \d+\b"] }));
+        assert!(filter.matches("DAY 12 of the challenge"));
+        assert!(!filter.matches("Sunday stream"));
+
+        let invalid = serde_json::from_value::<TitleFilter>(json!({ "regexes": ["(unclosed"] }));
+        assert!(invalid
+            .unwrap_err()
+            .to_string()
+            .contains("invalid title regex"));
+    }
+
+    #[test]
+    fn excludes_take_priority_over_includes() {
+        let filter = title_filter(json!({
+            "keywords": ["speedrun"],
+            "exclude_keywords": ["Rerun"],
+            "exclude_regexes": [r"^\[vod\]"]
+        }));
+        assert!(filter.matches("Speedrun practice"));
+        assert!(!filter.matches("RERUN: speedrun practice"));
+        assert!(!filter.matches("[VOD] speedrun practice"));
+    }
+
+    #[test]
+    fn filter_without_includes_matches_nothing() {
+        let filter = title_filter(json!({ "exclude_keywords": ["rerun"] }));
+        assert!(!filter.matches("Speedrun practice"));
+        assert!(!filter.allows_title_change("Speedrun practice"));
+    }
+
+    #[test]
+    fn every_go_live_passes_unless_a_match_is_required() {
+        let filter =
+            title_filter(json!({ "keywords": ["speedrun"], "exclude_keywords": ["rerun"] }));
+        assert!(filter.allows_live("Just chatting"));
+        assert!(filter.allows_live("Rerun of yesterday"));
+
+        let required = title_filter(json!({
+            "keywords": ["speedrun"],
+            "exclude_keywords": ["rerun"],
+            "require_match_for_live": true
+        }));
+        assert!(required.allows_live("Speedrun practice"));
+        assert!(!required.allows_live("Just chatting"));
+        assert!(!required.allows_live("Speedrun rerun"));
+
+        // Only excludes: everything else goes through
+        let excludes_only = title_filter(json!({
+            "exclude_keywords": ["rerun"],
+            "require_match_for_live": true
+        }));
+        assert!(excludes_only.allows_live("Just chatting"));
+        assert!(!excludes_only.allows_live("Rerun of yesterday"));
+    }
+
+    #[test]
+    fn title_changes_need_a_match_and_notify_on_change() {
+        let filter = title_filter(json!({ "keywords": ["speedrun"] }));
+        assert!(filter.allows_title_change("Speedrun time"));
+        assert!(!filter.allows_title_change("Just chatting"));
+
+        let quiet = title_filter(json!({ "keywords": ["speedrun"], "notify_on_change": false }));
+        assert!(!quiet.allows_title_change("Speedrun time"));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let typo = serde_json::from_value::<TitleFilter>(json!({ "keyword": ["speedrun"] }));
+        assert!(typo.is_err());
+    }
+
+    fn game_filter(config: serde_json::Value) -> GameFilter {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn allowlist_matches_names_and_ids() {
+        let filter = game_filter(json!({ "mode": "allowlist", "games": ["minecraft", "509658"] }));
+        assert!(filter.allows("27471", "Minecraft"));
+        assert!(filter.allows("509658", "Just Chatting"));
+        assert!(!filter.allows("32982", "Grand Theft Auto V"));
+        // IDs match exactly, not by name
+        assert!(!filter.allows("1", "509658x"));
+    }
+
+    #[test]
+    fn blocklist_matches_names_and_ids() {
+        let filter =
+            game_filter(json!({ "mode": "blocklist", "games": ["Just Chatting", "27471"] }));
+        assert!(!filter.allows("509658", "just chatting"));
+        assert!(!filter.allows("27471", "Minecraft"));
+        assert!(filter.allows("32982", "Grand Theft Auto V"));
+    }
+
+    #[test]
+    fn non_ascii_names_match_case_insensitively() {
+        let filter =
+            game_filter(json!({ "mode": "allowlist", "games": ["POKÉMON Legends: Z-A", "原神"] }));
+        assert!(filter.allows("1", "Pokémon Legends: Z-A"));
+        assert!(filter.allows("2", "原神"));
+        assert!(!filter.allows("3", "Pokemon Legends: Z-A"));
+    }
+
+    #[test]
+    fn off_allows_every_game() {
+        let filter = game_filter(json!({ "games": ["Minecraft"] }));
+        assert_eq!(filter.mode, GameFilterMode::Off);
+        assert!(filter.allows("27471", "Minecraft"));
+        assert!(filter.notify_on_switch_into);
+    }
+}
@@ -0,0 +1,122 @@
+// src/image_cache.rs
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// Downloads give up after this long; a slow CDN only means a notification without an image.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// On-disk cache for avatars and stream thumbnails.
+///
+/// Lookups never touch the network: they return whatever file is on disk (even an
+/// expired one) and refresh missing or expired files in the background, so images
+/// show up from the next notification on.
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    dir: PathBuf,
+    http: reqwest::Client,
+    // Keys with a download in progress, so a key is only fetched once at a time
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ImageCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            http: reqwest::Client::new(),
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    fn path_for(&self, key: &str, url: &str) -> PathBuf {
+        let extension = Path::new(url)
+            .extension()
+            .and_then(|e| e.to_str())
+            .filter(|e| e.len() <= 4)
+            .unwrap_or("img");
+        self.dir.join(format!("{}.{}", key, extension))
+    }
+
+    /// Returns the cached image for `key`, if there is one. Starts a background
+    /// download of `url` when the file is missing or older than `max_age`.
+    /// Must be called from within the Tokio runtime.
+    pub fn get(&self, key: &str, url: &str, max_age: Duration) -> Option<PathBuf> {
+        if url.is_empty() {
+            return None;
+        }
+        let path = self.path_for(key, url);
+        let age = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or_default()
+            });
+
+        if age.is_none_or(|age| age >= max_age) {
+            self.refresh(key, url, path.clone());
+        }
+        age.map(|_| path)
+    }
+
+    fn refresh(&self, key: &str, url: &str, path: PathBuf) {
+        if !self
+            .in_flight
+            .lock()
+            .expect("image cache mutex poisoned")
+            .insert(key.to_string())
+        {
+            return;
+        }
+
+        let cache = self.clone();
+        let key = key.to_string();
+        let url = url.to_string();
+        tokio::spawn(async move {
+            match cache.download(&url, &path).await {
+                Ok(()) => debug!(key, "Cached image"),
+                Err(e) => warn!(key, "Failed to cache image from {}: {}", url, e),
+            }
+            cache
+                .in_flight
+                .lock()
+                .expect("image cache mutex poisoned")
+                .remove(&key);
+        });
+    }
+
+    /// Downloads into a temporary file and renames it into place, so a notification
+    /// never picks up a half-written image.
+    async fn download(
+        &self,
+        url: &str,
+        path: &Path,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let bytes = self
+            .http
+            .get(url)
+            .timeout(DOWNLOAD_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let tmp_path = path.with_extension("part");
+        tokio::fs::write(&tmp_path, &bytes).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+}
+
+/// Fills in the `{width}` and `{height}` placeholders of a Helix thumbnail URL.
+pub fn thumbnail_url(template: &str, width: u32, height: u32) -> String {
+    template
+        .replace("{width}", &width.to_string())
+        .replace("{height}", &height.to_string())
+}
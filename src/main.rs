@@ -1,39 +1,68 @@
+mod discord_sink;
+mod eventsub;
+mod eventsub_webhook;
+mod filters;
+mod image_cache;
 mod notifications;
+mod push_sink;
+mod quiet_hours;
+mod rate_limit;
+mod settings;
+mod stream_state;
+mod templates;
+// Without the `tray` feature nothing reads the updates
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+mod tray;
+#[cfg(feature = "tray")]
+mod tray_icon;
 mod twitch_api;
+mod webhook_sink;
 
-use serde::Deserialize;
+use chrono::NaiveDate;
+use futures_util::future::join_all;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::runtime::Runtime;
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc;
-use tokio::time::interval;
+use tokio::time::{interval, interval_at, sleep_until, Instant};
 use tracing::{debug, error, info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
-use tray_item::TrayItem;
 
 // Import the client and its error type
-use crate::twitch_api::{ApiError, Stream, TwitchClient, User};
+use crate::eventsub::{ChannelEvent, EventSubClient};
+use crate::notifications::{NotificationGroup, SessionSummary, SinkEntry, StreamNotification};
+use crate::quiet_hours::{Clock, Digest, QuietAction, SystemClock};
+use crate::settings::{load_settings, GroupingMode, NotificationUrgency, Settings};
+use crate::stream_state::{format_duration, StreamEvent, StreamTracker};
+use crate::templates::Escape;
+use crate::tray::{LiveStream, NotifyState, TrayHandle, TrayUpdate};
+#[cfg(feature = "tray")]
+use crate::tray_icon::Tray;
+use crate::twitch_api::{
+    ApiError, EventSubTransport, Stream, TwitchClient, User, TOKEN_VALIDATION_INTERVAL,
+};
 
 // For control messages TO the monitor task
 #[derive(Debug)]
-enum AppMessage {
+pub enum AppMessage {
     Quit,
+    // Stop sending notifications until resumed; checks keep running
+    Pause,
+    Resume,
+    // Pause, then resume automatically after the given time
+    SnoozeFor(Duration),
+    // Check the streams right away instead of waiting for the interval
+    CheckNow,
+    ReloadConfig,
+    // From the "Mute for today" notification action
+    MuteForToday { login: String },
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct Settings {
-    pub twitch_client_id: String,
-    pub twitch_client_secret: String,
-    pub streamers: Vec<String>,
-    #[serde(default = "default_check_interval")]
-    pub check_interval_seconds: u64,
-    // TODO: Add systray enable/disable config
-}
-
-fn default_check_interval() -> u64 {
-    60 // Default to 60 seconds
-}
+/// Streamers muted from a notification, keyed by login, with the day the mute applies to.
+type MutedToday = HashMap<String, NaiveDate>;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -46,15 +75,13 @@ pub enum Error {
     #[error("Twitch API error: {0}")]
     Api(twitch_api::ApiError),
 
-    #[error("Tray icon error: {0}")]
-    Tray(tray_item::TIError),
-
     #[error("Task join error: {0}")]
     Join(tokio::task::JoinError),
 
     #[error("Message channel send error")]
     ChannelSend,
 
+    #[cfg(feature = "tray")]
     #[error("GTK initialization failed: {0}")]
     GtkInit(#[from] gtk::glib::BoolError), // Add variant for GTK init error
 }
@@ -78,12 +105,6 @@ impl From<twitch_api::ApiError> for Error {
     }
 }
 
-impl From<tray_item::TIError> for Error {
-    fn from(err: tray_item::TIError) -> Self {
-        Error::Tray(err)
-    }
-}
-
 impl From<tokio::task::JoinError> for Error {
     fn from(err: tokio::task::JoinError) -> Self {
         Error::Join(err)
@@ -93,58 +114,558 @@ impl From<tokio::task::JoinError> for Error {
 // Make the result type alias use our top-level Error
 type Result<T> = std::result::Result<T, Error>;
 
-pub fn load_settings() -> Result<Settings> {
-    // Use the Result alias
-    let config_file_name = "config.toml";
+/// A notification that got past the filters, and when it goes out.
+enum Pending {
+    Now(StreamNotification),
+    // Held for the digest at the end of quiet hours
+    Digest(StreamNotification),
+}
 
-    info!(
-        "Attempting to load configuration from '{}'",
-        config_file_name
-    );
+/// Turns stream events into notifications and hands them to the configured sinks.
+struct Notifier {
+    settings: Settings,
+    clock: Box<dyn Clock>,
+    // Paused or snoozed from the tray
+    paused: bool,
+    muted_today: MutedToday,
+    // Notifications held by quiet hours
+    digest: Digest,
+    // Profile image URLs, keyed by user ID
+    avatar_urls: HashMap<String, String>,
+    sinks: Vec<SinkEntry>,
+}
+
+impl Notifier {
+    fn new(
+        settings: Settings,
+        sinks: Vec<SinkEntry>,
+        users: &[User],
+        clock: Box<dyn Clock>,
+    ) -> Self {
+        let avatar_urls = users
+            .iter()
+            .filter(|u| !u.profile_image_url.is_empty())
+            .map(|u| (u.id.clone(), u.profile_image_url.clone()))
+            .collect();
+        Self {
+            settings,
+            clock,
+            paused: false,
+            muted_today: MutedToday::new(),
+            digest: Digest::default(),
+            avatar_urls,
+            sinks,
+        }
+    }
 
-    let settings = config::Config::builder()
-        // Look for `config.toml` in the current directory
-        .add_source(config::File::with_name(config_file_name).required(true))
-        // Add environment variable overrides (optional)
-        // e.g., `APP_TWITCH_CLIENT_ID=...` would override `twitch_client_id`
-        .add_source(
-            config::Environment::with_prefix("APP")
-                .separator("__")
-                .ignore_empty(true),
+    /// Switches to reloaded settings, keeping mutes, held notifications and the
+    /// sinks whose configuration didn't change.
+    async fn reload(
+        &mut self,
+        settings: Settings,
+        tx_app: &mpsc::Sender<AppMessage>,
+        users: &[User],
+    ) {
+        let current = std::mem::take(&mut self.sinks);
+        self.sinks = notifications::reload_sinks(
+            current,
+            &self.settings,
+            &settings,
+            tx_app,
+            users,
+            Duration::from_secs(settings.shutdown_timeout_seconds),
         )
-        .build()?;
+        .await;
+        self.settings = settings;
+    }
+
+    fn today(&self) -> NaiveDate {
+        self.clock.now().with_timezone(&chrono::Local).date_naive()
+    }
+
+    fn mute_for_today(&mut self, login: String) {
+        let today = self.today();
+        self.muted_today.insert(login, today);
+    }
+
+    /// Lets the sinks see every poll result, e.g. to keep cached thumbnails fresh.
+    fn observe_live_streams(&self, streams: &[Stream]) {
+        for entry in &self.sinks {
+            entry.sink().observe_live_streams(streams);
+        }
+    }
+
+    /// Sends the notification for a stream lifecycle event. This is the notification
+    /// path shared by the poller and EventSub.
+    fn notify_event(&mut self, event: &StreamEvent) {
+        match self.notification_for(event) {
+            Some(Pending::Now(notification)) => {
+                for entry in self.sinks.iter().filter(|e| e.accepts(notification.kind)) {
+                    entry.sink().send(&notification);
+                }
+            }
+            Some(Pending::Digest(notification)) => self.digest.hold(notification),
+            None => {}
+        }
+        self.end_streams(std::slice::from_ref(event));
+    }
+
+    /// Sends the notifications for the events of one check. When there are more
+    /// than `[grouping]` allows, each sink gets them as a group instead.
+    fn notify_events(&mut self, events: &[StreamEvent]) {
+        let mut notifications = Vec::new();
+        for event in events {
+            match self.notification_for(event) {
+                Some(Pending::Now(notification)) => notifications.push(notification),
+                Some(Pending::Digest(notification)) => self.digest.hold(notification),
+                None => {}
+            }
+        }
+
+        let grouping = &self.settings.grouping;
+
+        for entry in &self.sinks {
+            let accepted: Vec<&StreamNotification> = notifications
+                .iter()
+                .filter(|n| entry.accepts(n.kind))
+                .collect();
+            let groupable = accepted
+                .iter()
+                .filter(|n| grouping.events.contains(&n.kind))
+                .count();
+            if grouping.mode == GroupingMode::Off || groupable <= grouping.threshold {
+                for notification in accepted {
+                    entry.sink().send(notification);
+                }
+                continue;
+            }
+
+            let (grouped, single): (Vec<_>, Vec<_>) = accepted
+                .into_iter()
+                .partition(|n| grouping.events.contains(&n.kind));
+            info!(
+                "(Monitor Task) Grouping {} notifications for the {} sink",
+                grouped.len(),
+                entry.sink().name()
+            );
+            let group = NotificationGroup::new(grouped.into_iter().cloned().collect(), grouping);
+            entry.sink().send_group(&group);
+            for notification in single {
+                entry.sink().send(notification);
+            }
+        }
+        self.end_streams(events);
+    }
+
+    /// Sends the notifications held by quiet hours that are over for their streamer,
+    /// as one digest per sink.
+    fn flush_digest(&mut self) {
+        if self.paused {
+            return;
+        }
+        let now = self.clock.now();
+        let due = self.digest.take_due(|n| {
+            let quiet = self
+                .settings
+                .profile(&n.stream.user_login)
+                .quiet_action(now);
+            matches!(quiet, Some(QuietAction::Digest | QuietAction::Suppress))
+        });
+        if due.is_empty() {
+            return;
+        }
+
+        info!(
+            "(Monitor Task) Quiet hours are over, sending {} held notifications",
+            due.len()
+        );
+        let grouping = &self.settings.grouping;
+        for entry in &self.sinks {
+            let accepted: Vec<StreamNotification> = due
+                .iter()
+                .filter(|n| entry.accepts(n.kind))
+                .cloned()
+                .collect();
+            if accepted.len() < 2 || grouping.mode == GroupingMode::Off {
+                for notification in &accepted {
+                    entry.sink().send(notification);
+                }
+                continue;
+            }
+            let summary = format!("{} notifications during quiet hours", accepted.len());
+            let group = NotificationGroup::new(accepted, grouping).with_summary(summary);
+            entry.sink().send_group(&group);
+        }
+    }
+
+    /// Closes the sinks and waits up to `timeout` for their deliveries in flight,
+    /// including the webhook queue writes.
+    async fn shutdown(self, timeout: Duration) {
+        let pending: Vec<_> = self
+            .sinks
+            .into_iter()
+            .flat_map(|entry| entry.into_sink().shutdown())
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+        info!(
+            "(Monitor Task) Waiting up to {}s for {} notification deliveries...",
+            timeout.as_secs(),
+            pending.len()
+        );
+        if tokio::time::timeout(timeout, join_all(pending))
+            .await
+            .is_err()
+        {
+            warn!("(Monitor Task) Notification deliveries still running at shutdown were cut off.");
+        }
+    }
+
+    /// Tells the sinks about the streams that ended, after their notifications went out.
+    fn end_streams(&self, events: &[StreamEvent]) {
+        for event in events {
+            if let StreamEvent::WentOffline { stream, .. } = event {
+                for entry in &self.sinks {
+                    entry.sink().stream_ended(stream);
+                }
+            }
+        }
+    }
+
+    /// The notification for a stream lifecycle event, using the streamer's effective
+    /// settings, or None if filters, mutes or quiet hours suppress it.
+    fn notification_for(&self, event: &StreamEvent) -> Option<Pending> {
+        let stream = event.stream();
+        let profile = self.settings.profile(&stream.user_login);
+        let templates = &self.settings.templates;
+        let mut previous_game = None;
+        let mut previous_title = None;
+        let mut offline_for = None;
+        let mut session = None;
+
+        let template = match event {
+            StreamEvent::WentLive { stream } => {
+                info!(
+                    "{} just went live playing {}!",
+                    stream.user_name, stream.game_name
+                );
+                if !profile
+                    .game_filter
+                    .allows(&stream.game_id, &stream.game_name)
+                {
+                    debug!("Game is filtered out, not notifying.");
+                    return None;
+                }
+                if !profile.title_filter.allows_live(&stream.title) {
+                    debug!("Title doesn't match the filter, not notifying.");
+                    return None;
+                }
+                &templates.live
+            }
+            StreamEvent::GameChanged {
+                stream,
+                previous_game_id,
+                previous_game: previous,
+            } => {
+                info!(
+                    "{} changed game from {} to {}!",
+                    stream.user_name, previous, stream.game_name
+                );
+                let game_filter = profile.game_filter;
+                if !game_filter.allows(&stream.game_id, &stream.game_name) {
+                    debug!("Game is filtered out, not notifying.");
+                    return None;
+                }
+                previous_game = Some(previous.clone());
+                if game_filter.allows(previous_game_id, previous) {
+                    &templates.game
+                } else if game_filter.notify_on_switch_into {
+                    // Their go-live was filtered out, so this is the first alert for the session
+                    &templates.switched_into_game
+                } else {
+                    debug!("Switched into an allowed game mid-stream, not notifying.");
+                    return None;
+                }
+            }
+            StreamEvent::TitleChanged {
+                stream,
+                previous_title: previous,
+            } => {
+                debug!(
+                    "{} changed title from {:?} to {:?}",
+                    stream.user_name, previous, stream.title
+                );
+                if !profile.title_filter.allows_title_change(&stream.title) {
+                    return None;
+                }
+                info!("{} changed title to a matching one!", stream.user_name);
+                previous_title = Some(previous.clone());
+                &templates.title
+            }
+            StreamEvent::BackOnline {
+                stream,
+                offline_for: gap,
+            } => {
+                info!(
+                    "{} is back online after {}s.",
+                    stream.user_name,
+                    gap.as_secs()
+                );
+                offline_for = Some(*gap);
+                &templates.back_online
+            }
+            StreamEvent::WentOffline {
+                stream,
+                duration,
+                peak_viewers,
+                games_played,
+            } => {
+                info!(
+                    "{} went offline after {}.",
+                    stream.user_name,
+                    format_duration(*duration)
+                );
+                session = Some(SessionSummary {
+                    duration: *duration,
+                    peak_viewers: *peak_viewers,
+                    games_played: games_played.clone(),
+                });
+                &templates.offline
+            }
+        };
+
+        if profile.muted {
+            debug!("Streamer is muted, not notifying.");
+            return None;
+        }
+        if self.muted_today.get(&stream.user_login) == Some(&self.today()) {
+            debug!("Streamer is muted for today, not notifying.");
+            return None;
+        }
+        if !profile.notifies(event.kind()) {
+            debug!(kind = ?event.kind(), "Event kind not enabled for this streamer, not notifying.");
+            return None;
+        }
+        if self.paused {
+            info!("Notifications are paused, not notifying.");
+            return None;
+        }
+        let quiet = profile.quiet_action(self.clock.now());
+        if quiet == Some(QuietAction::Suppress) {
+            info!("Quiet hours, not notifying.");
+            return None;
+        }
+
+        let images = &self.settings.images;
+        let mut notification = StreamNotification {
+            kind: event.kind(),
+            stream: stream.clone(),
+            display_name: profile.display_name(stream).to_string(),
+            summary: String::new(),
+            body: String::new(),
+            urgency: match quiet {
+                Some(QuietAction::LowUrgency) => NotificationUrgency::Low,
+                _ => profile.urgency_for(event.kind()),
+            },
+            timeout_seconds: profile.timeout_for(event.kind()),
+            icon: profile.icon.map(str::to_string),
+            url: notifications::stream_url(&stream.user_login),
+            avatar_url: self.avatar_urls.get(&stream.user_id).cloned(),
+            thumbnail_url: Some(image_cache::thumbnail_url(
+                &stream.thumbnail_url,
+                images.thumbnail_width,
+                images.thumbnail_height,
+            ))
+            .filter(|url| !url.is_empty()),
+            previous_game,
+            previous_title,
+            offline_for,
+            session,
+        };
+        notification.summary = templates::render(&template.summary, &notification, Escape::None);
+        // An empty title or game would otherwise leave a blank last line
+        notification.body = templates::render(&template.body, &notification, Escape::None)
+            .trim_end()
+            .to_string();
+
+        if quiet == Some(QuietAction::Digest) {
+            info!("Quiet hours, holding the notification for the digest.");
+            return Some(Pending::Digest(notification));
+        }
+        Some(Pending::Now(notification))
+    }
+}
+
+/// Builds the full `Stream` for a `stream.online` event, which carries no game or title.
+/// `/streams` may not list a stream that just started, so channel information is the fallback.
+async fn resolve_online_stream(
+    twitch_client: &TwitchClient,
+    event: &ChannelEvent,
+) -> Option<Stream> {
+    let ChannelEvent::Online {
+        user_id,
+        user_login,
+        user_name,
+        stream_id,
+        started_at,
+    } = event
+    else {
+        return None;
+    };
+
+    let ids = [user_id.clone()];
+    match twitch_client.get_streams_by_user_id(&ids).await {
+        Ok(streams) if !streams.is_empty() => return streams.into_iter().next(),
+        Ok(_) => debug!("(Monitor Task) Stream not listed yet, using channel information"),
+        Err(e) => warn!(
+            "(Monitor Task) Failed to fetch stream for online event: {}",
+            e
+        ),
+    }
+
+    let channel = match twitch_client.get_channel_information(&ids).await {
+        Ok(channels) => channels.into_iter().next(),
+        Err(e) => {
+            warn!("(Monitor Task) Failed to fetch channel information: {}", e);
+            None
+        }
+    };
+
+    Some(Stream {
+        id: stream_id.clone(),
+        user_id: user_id.clone(),
+        user_login: user_login.clone(),
+        user_name: user_name.clone(),
+        game_id: channel
+            .as_ref()
+            .map(|c| c.game_id.clone())
+            .unwrap_or_default(),
+        game_name: channel
+            .as_ref()
+            .map(|c| c.game_name.clone())
+            .unwrap_or_default(),
+        title: channel.map(|c| c.title).unwrap_or_default(),
+        stream_type: "live".to_string(),
+        viewer_count: 0,
+        started_at: started_at.clone(),
+        thumbnail_url: String::new(),
+    })
+}
+
+/// Applies an EventSub event to the stream tracker and returns the resulting transitions.
+async fn handle_channel_event(
+    twitch_client: &TwitchClient,
+    tracker: &mut StreamTracker,
+    event: ChannelEvent,
+) -> Vec<StreamEvent> {
+    match &event {
+        ChannelEvent::Online { user_id, .. } => {
+            if tracker.is_live(user_id) {
+                return Vec::new(); // The poller got there first
+            }
+            match resolve_online_stream(twitch_client, &event).await {
+                Some(stream) => tracker.observe_live_from_eventsub(stream),
+                None => Vec::new(),
+            }
+        }
+        ChannelEvent::Offline {
+            user_id, user_name, ..
+        } => {
+            debug!("{} ended their stream", user_name);
+            tracker.observe_offline(user_id).into_iter().collect()
+        }
+        ChannelEvent::Update {
+            user_id,
+            user_name,
+            title,
+            game_id,
+            game_name,
+            ..
+        } => {
+            debug!("{} updated their channel", user_name);
+            tracker.apply_channel_update(user_id, title, game_id, game_name)
+        }
+    }
+}
+
+/// Sends the tracker's live streams to the tray menu, sorted by display name.
+fn update_tray(tray: &TrayHandle, settings: &Settings, tracker: &StreamTracker) {
+    let mut live: Vec<LiveStream> = tracker
+        .live_streams()
+        .into_iter()
+        .map(|(stream, uptime)| LiveStream {
+            display_name: settings
+                .profile(&stream.user_login)
+                .display_name(stream)
+                .to_string(),
+            stream: stream.clone(),
+            uptime,
+        })
+        .collect();
+    live.sort_by_key(|l| l.display_name.to_lowercase());
+    tray.send_latest(TrayUpdate::LiveStreams(live));
+}
 
-    // Deserialize the configuration
-    let settings: Settings = settings.try_deserialize()?;
+/// A short description of why monitoring failed, for the tray menu.
+#[cfg(feature = "tray")]
+fn describe_error(err: &Error) -> String {
+    match err {
+        Error::Api(ApiError::TwitchError { status, message })
+            if *status == reqwest::StatusCode::UNAUTHORIZED
+                || *status == reqwest::StatusCode::FORBIDDEN =>
+        {
+            format!("Twitch authentication failed: {}", message)
+        }
+        Error::Api(ApiError::MissingToken) => {
+            "Twitch authentication failed: no access token".to_string()
+        }
+        Error::Api(ApiError::Request(e)) if e.is_connect() || e.is_timeout() => {
+            format!("Twitch API unreachable: {}", e)
+        }
+        other => other.to_string(),
+    }
+}
 
-    Ok(settings)
+/// Pauses or resumes notifications and shows the new state in the tray.
+async fn set_notify_state(notifier: &mut Notifier, tray: &TrayHandle, state: NotifyState) {
+    notifier.paused = state != NotifyState::Active;
+    tray.send(TrayUpdate::State(state)).await;
 }
 
 // This function contains the core async logic
-async fn run_monitor(settings: Settings, mut rx_app: mpsc::Receiver<AppMessage>) -> Result<()> {
+async fn run_monitor(
+    mut settings: Settings,
+    tx_app: mpsc::Sender<AppMessage>,
+    mut rx_app: mpsc::Receiver<AppMessage>,
+    tray: TrayHandle,
+) -> Result<()> {
     // Create Twitch client
     info!("(Monitor Task) Initializing Twitch client...");
     let mut twitch_client = TwitchClient::new(
         settings.twitch_client_id.clone(),
         settings.twitch_client_secret.clone(),
-    )?;
+    )?
+    .with_max_concurrent_requests(settings.max_concurrent_requests)
+    .with_user_access_token(settings.eventsub.user_access_token.clone());
+    if let Some(url) = &settings.eventsub.subscriptions_url {
+        twitch_client = twitch_client.with_eventsub_subscriptions_url(url.clone());
+    }
+    let twitch_client = Arc::new(twitch_client);
 
-    // Authenticate with Twitch
+    // Authenticate with Twitch and validate the token, as Twitch requires on startup
     twitch_client.get_app_access_token().await?;
+    twitch_client.validate_token().await?;
     info!("(Monitor Task) Successfully authenticated with Twitch API.");
 
     // Get initial user data
-    let monitored_users: Vec<User> = if settings.streamers.is_empty() {
+    let logins = settings.logins();
+    let monitored_users: Vec<User> = if logins.is_empty() {
         info!("(Monitor Task) No streamers configured to monitor.");
         vec![]
     } else {
-        info!(
-            "(Monitor Task) Fetching user info for: {:?}",
-            settings.streamers
-        );
-        let users = twitch_client
-            .get_users_by_login(&settings.streamers)
-            .await?;
+        info!("(Monitor Task) Fetching user info for: {:?}", logins);
+        let users = twitch_client.get_users_by_login(&logins).await?;
         if users.is_empty() {
             warn!("(Monitor Task) No Twitch users found for the configured login names.");
         } else {
@@ -162,151 +683,345 @@ async fn run_monitor(settings: Settings, mut rx_app: mpsc::Receiver<AppMessage>)
     }
 
     let monitored_user_ids: Vec<String> = monitored_users.iter().map(|u| u.id.clone()).collect();
-    let mut previous_stream_states: HashMap<String, Stream> = HashMap::new();
+    let mut tracker = StreamTracker::new(Duration::from_secs(settings.restart_grace_seconds));
+    let sinks = notifications::build_sinks(&settings, &tx_app, &monitored_users);
+    let mut notifier = Notifier::new(
+        settings.clone(),
+        sinks,
+        &monitored_users,
+        Box::new(SystemClock),
+    );
+
+    // EventSub delivers go-live events instantly; polling below stays as the fallback
+    let (tx_events, mut rx_events) = mpsc::channel::<ChannelEvent>(100);
+    let mut eventsub_handles = Vec::new();
+    if settings.eventsub.enabled {
+        let (shards, uncovered) = eventsub::shard_user_ids(&monitored_user_ids);
+        info!(
+            "(Monitor Task) Starting {} EventSub WebSocket client(s)...",
+            shards.len()
+        );
+        if !uncovered.is_empty() {
+            warn!(
+                "(Monitor Task) EventSub WebSocket subscriptions are capped at a total cost of {}; {} streamer(s) are only polled.",
+                eventsub::MAX_TOTAL_COST,
+                uncovered.len()
+            );
+        }
+        for user_ids in shards {
+            let client = EventSubClient::new(
+                settings.eventsub.websocket_url.clone(),
+                twitch_client.clone(),
+                user_ids,
+                tx_events.clone(),
+            );
+            eventsub_handles.push(tokio::spawn(client.run()));
+        }
+    }
+    if settings.eventsub_webhook.enabled {
+        let webhook = &settings.eventsub_webhook;
+        info!("(Monitor Task) Starting EventSub webhook receiver...");
+        // Bind before subscribing: Twitch sends the verification challenge right away
+        let listener = tokio::net::TcpListener::bind(&webhook.listen_address).await?;
+        let router =
+            eventsub_webhook::router(&webhook.path, webhook.secret.clone(), tx_events.clone());
+        eventsub_handles.push(tokio::spawn(eventsub_webhook::serve(listener, router)));
+
+        let subscribe_client = twitch_client.clone();
+        let user_ids = monitored_user_ids.clone();
+        let transport = EventSubTransport::Webhook {
+            callback: webhook.callback_url.clone(),
+            secret: webhook.secret.clone(),
+        };
+        eventsub_handles.push(tokio::spawn(async move {
+            if let Err(e) =
+                eventsub::subscribe_all(&subscribe_client, &user_ids, &transport).await
+            {
+                error!("(Monitor Task) Failed to register EventSub webhook subscriptions: {}. Relying on polling.", e);
+            }
+        }));
+    }
+    // Only the transports hold senders, so the channel closes if none is enabled
+    drop(tx_events);
 
     info!(
         "(Monitor Task) Starting monitoring loop (checking every {} seconds)",
         settings.check_interval_seconds
     );
     let mut check_interval = interval(Duration::from_secs(settings.check_interval_seconds));
+    // Twitch requires the token to be validated hourly; the startup check covers the first hour
+    let mut validate_interval = interval_at(
+        Instant::now() + TOKEN_VALIDATION_INTERVAL,
+        TOKEN_VALIDATION_INTERVAL,
+    );
+    // When a snooze from the tray ends
+    let mut snoozed_until: Option<Instant> = None;
+    // Whether the tray shows an error from the last check
+    let mut check_failed = false;
+    // A message that interrupted a check, handled next
+    let mut pending_message: Option<AppMessage> = None;
 
-    // Main monitoring loop
-    loop {
+    // Main monitoring loop, until Quit or an error it can't recover from
+    let result = loop {
         tokio::select! {
-            _ = check_interval.tick() => {
+            _ = check_interval.tick(), if pending_message.is_none() => {
+                notifier.flush_digest();
                 debug!("(Monitor Task) Checking stream statuses...");
-                match twitch_client.get_streams_by_user_id(&monitored_user_ids).await {
+                // Rate-limit waits can hold a check up for a while; a message (above all
+                // Quit) interrupts it, and the check starts over once it is handled
+                let check = tokio::select! {
+                    result = twitch_client.get_streams_by_user_id(&monitored_user_ids) => result,
+                    Some(msg) = rx_app.recv() => {
+                        debug!("(Monitor Task) Check interrupted by a message, checking again after it.");
+                        pending_message = Some(msg);
+                        check_interval.reset_immediately();
+                        continue;
+                    }
+                };
+                match check {
                     Ok(live_streams) => {
-                        let current_streams_map: HashMap<String, Stream> = live_streams
-                            .into_iter()
-                            .map(|s| (s.user_id.clone(), s))
-                            .collect();
-
-                        for (user_id, current_stream) in &current_streams_map {
-                            match previous_stream_states.get(user_id) {
-                                Some(previous_stream) => {
-                                    if current_stream.game_id != previous_stream.game_id {
-                                        info!(
-                                            "{} changed game to {}!",
-                                            current_stream.user_name, current_stream.game_name
-                                        );
-                                        notifications::send_notification(
-                                            &format!(
-                                                "{} changed game to {}!",
-                                                current_stream.user_name,
-                                                current_stream.game_name
-                                            ),
-                                            "", // Body is empty for game change
-                                            Some(&current_stream.title), // Pass title
-                                        );
-                                    }
-                                }
-                                None => {
-                                    info!(
-                                        "{} just went live playing {}!",
-                                        current_stream.user_name, current_stream.game_name
-                                    );
-                                    notifications::send_notification(
-                                        &format!("{} just went live!", current_stream.user_name),
-                                        &format!("Playing: {}", current_stream.game_name),
-                                        Some(&current_stream.title), // Pass title
-                                    );
-                                }
-                            }
+                        notifier.observe_live_streams(&live_streams);
+                        let events = tracker.apply_poll(live_streams);
+                        notifier.notify_events(&events);
+                        update_tray(&tray, &settings, &tracker);
+                        if check_failed {
+                            check_failed = false;
+                            tray.send(TrayUpdate::ErrorCleared).await;
                         }
 
-                        previous_stream_states = current_streams_map;
+                        // Hold off the next check until the bucket refills if the budget runs low
+                        let low_budget = twitch_client
+                            .rate_limit_budget()
+                            .filter(|b| b.is_low() && b.reset_at > Instant::now() + check_interval.period());
+                        if let Some(budget) = low_budget {
+                            warn!(
+                                remaining = budget.remaining,
+                                limit = budget.limit,
+                                "(Monitor Task) Twitch API rate limit budget low. Slowing down polling."
+                            );
+                            check_interval.reset_at(budget.reset_at);
+                        }
                     }
                     Err(ApiError::Request(e)) if e.is_timeout() => {
                         warn!("(Monitor Task) Twitch API request timed out. Retrying next cycle.");
+                        check_failed = true;
+                        tray.send(TrayUpdate::Error("Twitch API request timed out".to_string())).await;
+                    }
+                    Err(ApiError::Request(e)) if e.is_connect() => {
+                        warn!("(Monitor Task) Twitch API unreachable: {}. Retrying next cycle.", e);
+                        check_failed = true;
+                        tray.send(TrayUpdate::Error(format!("Twitch API unreachable: {}", e))).await;
+                    }
+                    Err(ApiError::TwitchError { status, .. }) if status == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                        warn!("(Monitor Task) Twitch API rate limit still exceeded after retries. Retrying next cycle.");
+                        check_failed = true;
+                        tray.send(TrayUpdate::Error("Twitch API rate limit exceeded".to_string())).await;
                     }
                     Err(ApiError::TwitchError { status, .. }) if status.is_server_error() => {
                         warn!(status = %status, "(Monitor Task) Twitch API server error. Retrying next cycle.");
-                    }
-                    Err(ApiError::MissingToken) => {
-                        warn!("(Monitor Task) App Access Token missing or invalid. Attempting re-authentication...");
-                        if let Err(auth_err) = twitch_client.get_app_access_token().await {
-                            error!("(Monitor Task) Failed to re-authenticate with Twitch: {}. Exiting.", auth_err);
-                            return Err(auth_err.into());
-                        }
-                        info!("(Monitor Task) Successfully re-authenticated.");
-                        continue;
+                        check_failed = true;
+                        tray.send(TrayUpdate::Error(format!("Twitch API server error ({})", status))).await;
                     }
                     Err(e) => {
+                        // main() shows the error in the tray
                         error!("(Monitor Task) Unhandled error during stream check: {}. Exiting.", e);
-                        // Optionally send an error state to the tray before exiting
-                        // tx_tray.send(TrayUpdate::Error(format!("API Error: {}", e))).ok();
-                        return Err(e.into());
+                        break Err(e.into());
                     }
                 }
             }
-            Some(msg) = rx_app.recv() => {
+            _ = validate_interval.tick() => {
+                debug!("(Monitor Task) Validating App Access Token...");
+                // A failed validation is not fatal: the client refreshes the token on the next 401
+                if let Err(e) = twitch_client.validate_token().await {
+                    warn!("(Monitor Task) Hourly token validation failed: {}", e);
+                }
+            }
+            _ = sleep_until(snoozed_until.unwrap_or_else(Instant::now)), if snoozed_until.is_some() => {
+                info!("(Monitor Task) Snooze is over, resuming notifications.");
+                snoozed_until = None;
+                set_notify_state(&mut notifier, &tray, NotifyState::Active).await;
+            }
+            Some(event) = rx_events.recv() => {
+                let events = handle_channel_event(&twitch_client, &mut tracker, event).await;
+                for event in &events {
+                    notifier.notify_event(event);
+                }
+                if !events.is_empty() {
+                    update_tray(&tray, &settings, &tracker);
+                }
+            }
+            Some(msg) = next_app_message(&mut pending_message, &mut rx_app) => {
                 info!("(Monitor Task) Received message: {:?}", msg);
                 match msg {
                     AppMessage::Quit => {
                         info!("(Monitor Task) Quit message received, shutting down.");
-                        break; // Exit the loop
+                        break Ok(());
+                    }
+                    AppMessage::MuteForToday { login } => {
+                        info!("(Monitor Task) Muting {} until tomorrow.", login);
+                        notifier.mute_for_today(login);
                     }
+                    AppMessage::Pause => {
+                        info!("(Monitor Task) Pausing notifications.");
+                        snoozed_until = None;
+                        set_notify_state(&mut notifier, &tray, NotifyState::Paused).await;
+                    }
+                    AppMessage::Resume => {
+                        info!("(Monitor Task) Resuming notifications.");
+                        snoozed_until = None;
+                        set_notify_state(&mut notifier, &tray, NotifyState::Active).await;
+                    }
+                    AppMessage::SnoozeFor(duration) => {
+                        info!("(Monitor Task) Snoozing notifications for {}.", format_duration(duration));
+                        snoozed_until = Some(Instant::now() + duration);
+                        let until = chrono::Local::now() + duration;
+                        set_notify_state(&mut notifier, &tray, NotifyState::Snoozed { until }).await;
+                    }
+                    AppMessage::CheckNow => check_interval.reset_immediately(),
+                    AppMessage::ReloadConfig => match load_settings() {
+                        Ok(reloaded) => {
+                            for setting in settings.changes_needing_restart(&reloaded) {
+                                warn!("(Monitor Task) Changes to {} take effect after a restart.", setting);
+                            }
+                            if reloaded.notification_actions.default != settings.notification_actions.default {
+                                tray.send(TrayUpdate::OpenCommand(reloaded.notification_actions.default.clone())).await;
+                            }
+                            if reloaded.check_interval_seconds != settings.check_interval_seconds {
+                                check_interval = interval(Duration::from_secs(reloaded.check_interval_seconds));
+                            }
+                            notifier.reload(reloaded.clone(), &tx_app, &monitored_users).await;
+                            settings = reloaded;
+                            info!("(Monitor Task) Configuration reloaded.");
+                        }
+                        Err(e) => error!("(Monitor Task) Failed to reload the configuration, keeping the current one: {}", e),
+                    },
                 }
             }
         }
+    };
+
+    for handle in eventsub_handles {
+        handle.abort();
     }
-    Ok(())
+    notifier
+        .shutdown(Duration::from_secs(settings.shutdown_timeout_seconds))
+        .await;
+    result
 }
 
-// Main function now sets up tracing, loads config, spawns the async task,
-// and runs the systray loop.
-fn main() -> Result<()> {
-    // Initialize GTK first on the main thread
-    gtk::init()?;
+/// The message that interrupted a check, if any, or else the next one from the channel.
+async fn next_app_message(
+    pending: &mut Option<AppMessage>,
+    rx_app: &mut mpsc::Receiver<AppMessage>,
+) -> Option<AppMessage> {
+    match pending.take() {
+        Some(msg) => Some(msg),
+        None => rx_app.recv().await,
+    }
+}
 
-    // Initialize tracing subscriber
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Setting default tracing subscriber failed");
+/// Forwards SIGINT and SIGTERM to the monitor task as Quit, and SIGHUP as
+/// ReloadConfig. Returns when a Quit can't be delivered because the monitor task
+/// is gone. A second SIGINT or SIGTERM while shutting down exits right away.
+async fn forward_signals(tx_app: mpsc::Sender<AppMessage>) -> std::io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut quitting = false;
+    loop {
+        let message = tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                AppMessage::Quit
+            }
+            _ = terminate.recv() => AppMessage::Quit,
+            _ = hangup.recv() => AppMessage::ReloadConfig,
+        };
+        let quit = matches!(message, AppMessage::Quit);
+        if quit && quitting {
+            warn!("Second shutdown signal received, exiting without waiting.");
+            std::process::exit(1);
+        }
+        info!(
+            "Signal received, sending {:?} to the monitor task.",
+            message
+        );
+        if tx_app.send(message).await.is_err() {
+            if quit {
+                return Ok(());
+            }
+            warn!("Monitor task is gone, ignoring the signal.");
+        }
+        quitting |= quit;
+    }
+}
 
-    // Load settings (remains synchronous)
-    let settings = load_settings()?;
-    info!("Configuration loaded successfully!");
+/// Runs the monitor task without GTK, until it stops on its own or a shutdown
+/// signal arrives.
+fn run_headless(rt: &Runtime, settings: Settings) -> Result<()> {
+    let (tx_app, rx_app) = mpsc::channel::<AppMessage>(10);
+    rt.block_on(async move {
+        let monitor = tokio::spawn(run_monitor(
+            settings,
+            tx_app.clone(),
+            rx_app,
+            TrayHandle::headless(),
+        ));
+        tokio::spawn(async move {
+            if let Err(e) = forward_signals(tx_app).await {
+                error!("Failed to listen for signals: {}", e);
+            }
+        });
+        monitor.await?
+    })
+}
 
-    // Create a Tokio runtime for the async task
-    let rt = Runtime::new()?;
+/// Runs the monitor task on the runtime and the tray icon on this thread, until
+/// the monitor task shuts down after Quit or a signal.
+#[cfg(feature = "tray")]
+fn run_with_tray(rt: &Runtime, settings: Settings) -> Result<()> {
+    // Initialize GTK on the main thread
+    gtk::init()?;
 
-    // Create ONLY the app control channel
+    // Control messages to the monitor task, and state updates back to the tray
     let (tx_app, rx_app) = mpsc::channel::<AppMessage>(10);
+    let (tray_handle, rx_tray) = TrayHandle::channel(10);
 
     // Clone settings needed for the monitor task
     let monitor_settings = settings.clone();
 
-    // Spawn the async monitor task onto the Tokio runtime - pass only rx_app
+    // Notification actions send to the monitor task as well
+    let monitor_tx = tx_app.clone();
+
+    // Spawn the async monitor task onto the Tokio runtime.
+    // Monitoring stops on errors it can't recover from; the tray says why
+    let monitor_tray = tray_handle.clone();
     let monitor_handle = rt.spawn(async move {
-        if let Err(e) = run_monitor(monitor_settings, rx_app).await {
-            error!("Monitor task failed: {}", e);
+        let tray = monitor_tray.clone();
+        match run_monitor(monitor_settings, monitor_tx, rx_app, tray).await {
+            Ok(()) => monitor_tray.send(TrayUpdate::Quit).await,
+            Err(e) => {
+                error!("Monitor task failed: {}", e);
+                let message = format!("Monitoring stopped: {}", describe_error(&e));
+                monitor_tray.send(TrayUpdate::Error(message)).await;
+            }
+        }
+    });
+
+    // Signals take the same shutdown path as the Quit menu item
+    let signal_tx = tx_app.clone();
+    rt.spawn(async move {
+        match forward_signals(signal_tx).await {
+            // The monitor task is gone, so nothing else closes the tray
+            Ok(()) => tray_handle.send(TrayUpdate::Quit).await,
+            Err(e) => error!("Failed to listen for signals: {}", e),
         }
     });
 
     info!("Starting system tray icon...");
-    // Revert TrayItem creation to simple mutable variable
-    let mut tray = TrayItem::new(
-        "Twitch Notifier",
-        tray_item::IconSource::Resource("default-icon"),
-    )?;
-    tray.add_label("Twitch Notifier")?;
-
-    // Revert Quit callback - no Rc needed
-    let quit_tx = tx_app.clone();
-    tray.add_menu_item("Quit", move || {
-        info!("Quit menu item clicked.");
-        if quit_tx.blocking_send(AppMessage::Quit).is_err() {
-            error!("Failed to send Quit message to monitor task");
-        }
-        gtk::main_quit();
-    })?;
+    let tray = Tray::new(tx_app, &settings.notification_actions.default);
 
     info!("System tray started. Running GTK main loop.");
-    gtk::main();
+    tray.run(rx_tray);
 
     // gtk::main() has returned, meaning gtk::main_quit() was called.
     info!("GTK main loop finished.");
@@ -314,7 +1029,286 @@ fn main() -> Result<()> {
     // Wait for the monitor task to finish.
     info!("Waiting for monitor task to shut down...");
     rt.block_on(monitor_handle)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "tray"))]
+fn run_with_tray(rt: &Runtime, settings: Settings) -> Result<()> {
+    info!("Built without tray support, running headless.");
+    run_headless(rt, settings)
+}
+
+// Main function sets up tracing, loads config, and runs the monitor task, with
+// the systray loop unless running headless.
+fn main() -> Result<()> {
+    // Initialize tracing subscriber
+    let subscriber = FmtSubscriber::builder()
+        .with_max_level(Level::INFO)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("Setting default tracing subscriber failed");
+
+    // Load settings (remains synchronous)
+    let settings = load_settings()?;
+    info!("Configuration loaded successfully!");
+
+    // Create a Tokio runtime for the async task
+    let rt = Runtime::new()?;
+
+    // `--headless` wins over `tray = true`, e.g. for a one-off run over SSH
+    let headless = !settings.tray || std::env::args().skip(1).any(|arg| arg == "--headless");
+    if headless {
+        info!("Running headless, without the tray icon.");
+        run_headless(&rt, settings)?;
+    } else {
+        run_with_tray(&rt, settings)?;
+    }
     info!("Monitor task finished. Exiting.");
 
+    // Deliveries had their time in run_monitor; don't wait for stragglers such as DNS lookups
+    rt.shutdown_background();
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::NotificationSink;
+    use crate::quiet_hours::FixedClock;
+    use crate::settings::{SinkConfig, SinkKind};
+    use std::sync::Mutex;
+
+    /// Records the summaries of the notifications and groups it receives.
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl NotificationSink for Recorder {
+        fn name(&self) -> &'static str {
+            "recorder"
+        }
+
+        fn send(&self, notification: &StreamNotification) {
+            self.0.lock().unwrap().push(notification.summary.clone());
+        }
+
+        fn send_group(&self, group: &NotificationGroup) {
+            self.0.lock().unwrap().push(group.summary.clone());
+        }
+    }
+
+    /// A notifier for `cooler_user` with the given extra settings, sending to a recorder.
+    fn notifier(config: &str, clock: &FixedClock) -> (Notifier, Arc<Mutex<Vec<String>>>) {
+        let settings = Settings::from_toml(&format!(
+            r#"
+            twitch_client_id = "id"
+            twitch_client_secret = "secret"
+            streamers = ["cooler_user"]
+            {}
+            "#,
+            config
+        ))
+        .unwrap();
+        let sent = Arc::default();
+        let sink = SinkEntry::new(
+            SinkConfig {
+                events: None,
+                kind: SinkKind::Log,
+            },
+            Box::new(Recorder(Arc::clone(&sent))),
+        );
+        let notifier = Notifier::new(settings, vec![sink], &[], Box::new(clock.clone()));
+        (notifier, sent)
+    }
+
+    fn went_live() -> StreamEvent {
+        StreamEvent::WentLive {
+            stream: StreamNotification::sample().stream,
+        }
+    }
+
+    #[test]
+    fn digest_window_wrapping_past_midnight_flushes_at_its_end() {
+        let clock = FixedClock::at("2026-10-16T23:30:00Z");
+        let (mut notifier, sent) = notifier(
+            r#"quiet_hours = { start = "23:00", end = "07:00", action = "digest", timezone = "UTC" }"#,
+            &clock,
+        );
+
+        assert!(matches!(
+            notifier.notification_for(&went_live()),
+            Some(Pending::Digest(_))
+        ));
+        notifier.notify_event(&went_live());
+        assert!(sent.lock().unwrap().is_empty());
+
+        clock.set("2026-10-17T06:59:00Z");
+        notifier.flush_digest();
+        assert!(sent.lock().unwrap().is_empty());
+
+        clock.set("2026-10-17T07:00:00Z");
+        assert!(matches!(
+            notifier.notification_for(&went_live()),
+            Some(Pending::Now(_))
+        ));
+        notifier.flush_digest();
+        assert_eq!(*sent.lock().unwrap(), ["Cooler_User just went live!"]);
+    }
+
+    #[test]
+    fn digest_stays_held_while_a_suppress_window_follows() {
+        let clock = FixedClock::at("2026-10-16T22:30:00Z");
+        let (mut notifier, sent) = notifier(
+            r#"quiet_hours = [
+                { start = "22:00", end = "23:00", action = "digest", timezone = "UTC" },
+                { start = "23:00", end = "07:00", action = "suppress", timezone = "UTC" },
+            ]"#,
+            &clock,
+        );
+        notifier.notify_event(&went_live());
+
+        clock.set("2026-10-16T23:30:00Z");
+        assert!(notifier.notification_for(&went_live()).is_none());
+        notifier.flush_digest();
+        assert!(sent.lock().unwrap().is_empty());
+
+        clock.set("2026-10-17T07:00:00Z");
+        notifier.flush_digest();
+        assert_eq!(sent.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn digest_in_a_named_zone_follows_the_dst_change() {
+        // Europe/Berlin leaves summer time (UTC+2) for UTC+1 at 03:00 on 2026-10-25
+        let clock = FixedClock::at("2026-10-24T21:00:00Z");
+        let (mut notifier, sent) = notifier(
+            r#"quiet_hours = { start = "22:00", end = "06:00", action = "digest", timezone = "Europe/Berlin" }"#,
+            &clock,
+        );
+        notifier.notify_event(&went_live());
+
+        // 05:30 local: still quiet, though it would be 06:30 at the summer offset
+        clock.set("2026-10-25T04:30:00Z");
+        notifier.flush_digest();
+        assert!(sent.lock().unwrap().is_empty());
+
+        clock.set("2026-10-25T05:00:00Z");
+        notifier.flush_digest();
+        assert_eq!(sent.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn low_urgency_window_sends_right_away() {
+        let clock = FixedClock::at("2026-10-16T23:30:00Z");
+        let (notifier, _sent) = notifier(
+            r#"quiet_hours = { start = "23:00", end = "07:00", action = "low_urgency", timezone = "UTC" }"#,
+            &clock,
+        );
+
+        match notifier.notification_for(&went_live()) {
+            Some(Pending::Now(notification)) => {
+                assert_eq!(notification.urgency, NotificationUrgency::Low)
+            }
+            _ => panic!("expected a notification to send now"),
+        }
+    }
+
+    #[test]
+    fn paused_notifier_keeps_the_digest() {
+        let clock = FixedClock::at("2026-10-16T23:30:00Z");
+        let (mut notifier, sent) = notifier(
+            r#"quiet_hours = { start = "23:00", end = "07:00", action = "digest", timezone = "UTC" }"#,
+            &clock,
+        );
+        notifier.notify_event(&went_live());
+        notifier.paused = true;
+
+        clock.set("2026-10-17T08:00:00Z");
+        notifier.flush_digest();
+        assert!(sent.lock().unwrap().is_empty());
+
+        notifier.paused = false;
+        notifier.flush_digest();
+        assert_eq!(sent.lock().unwrap().len(), 1);
+    }
+
+    fn game_change(from: (&str, &str), to: (&str, &str)) -> StreamEvent {
+        let mut stream = StreamNotification::sample().stream;
+        stream.game_id = to.0.to_string();
+        stream.game_name = to.1.to_string();
+        StreamEvent::GameChanged {
+            stream,
+            previous_game_id: from.0.to_string(),
+            previous_game: from.1.to_string(),
+        }
+    }
+
+    #[test]
+    fn switching_into_an_allowed_game_is_the_first_alert() {
+        let clock = FixedClock::at("2026-10-17T12:00:00Z");
+        let (notifier, _sent) = notifier(
+            r#"game_filter = { mode = "allowlist", games = ["Minecraft"] }"#,
+            &clock,
+        );
+
+        // The go-live in another game was filtered out...
+        let mut live = StreamNotification::sample().stream;
+        live.game_name = "Just Chatting".to_string();
+        assert!(notifier
+            .notification_for(&StreamEvent::WentLive { stream: live })
+            .is_none());
+
+        // ...so switching into an allowed one announces the stream
+        let switch = game_change(("509658", "Just Chatting"), ("27471", "Minecraft"));
+        match notifier.notification_for(&switch) {
+            Some(Pending::Now(n)) => assert_eq!(n.previous_game.as_deref(), Some("Just Chatting")),
+            _ => panic!("expected a notification for the switch"),
+        }
+
+        // Leaving it is filtered out again
+        let leave = game_change(("27471", "Minecraft"), ("509658", "Just Chatting"));
+        assert!(notifier.notification_for(&leave).is_none());
+    }
+
+    #[test]
+    fn switching_into_an_allowed_game_can_be_ignored() {
+        let clock = FixedClock::at("2026-10-17T12:00:00Z");
+        let (notifier, _sent) = notifier(
+            r#"game_filter = { mode = "allowlist", games = ["Minecraft", "Terraria"], notify_on_switch_into = false }"#,
+            &clock,
+        );
+
+        let switch = game_change(("509658", "Just Chatting"), ("27471", "Minecraft"));
+        assert!(notifier.notification_for(&switch).is_none());
+
+        // Between two allowed games it is an ordinary game change
+        let between = game_change(("27471", "Minecraft"), ("31376", "Terraria"));
+        assert!(matches!(
+            notifier.notification_for(&between),
+            Some(Pending::Now(_))
+        ));
+    }
+
+    fn went_live_as(login: &str) -> StreamEvent {
+        let mut stream = StreamNotification::sample().stream;
+        stream.user_id = login.to_string();
+        stream.user_login = login.to_string();
+        stream.user_name = login.to_string();
+        StreamEvent::WentLive { stream }
+    }
+
+    #[test]
+    fn grouping_starts_above_the_threshold() {
+        let clock = FixedClock::at("2026-10-17T12:00:00Z");
+        let (mut notifier, sent) = notifier("grouping = { threshold = 2 }", &clock);
+
+        notifier.notify_events(&[went_live_as("a"), went_live_as("b")]);
+        assert_eq!(
+            *sent.lock().unwrap(),
+            ["a just went live!", "b just went live!"]
+        );
+
+        sent.lock().unwrap().clear();
+        notifier.notify_events(&[went_live_as("c"), went_live_as("d"), went_live_as("e")]);
+        assert_eq!(*sent.lock().unwrap(), ["3 streamers went live"]);
+    }
+}
@@ -1,36 +1,902 @@
-use notify_rust::Notification;
-use tracing::{error, info};
-
-/// Sends a desktop notification.
-pub fn send_notification(summary: &str, body_content: &str, title: Option<&str>) {
-    // Construct the final body, including the title if present
-    let final_body = if let Some(t) = title {
-        if body_content.is_empty() {
-            // If original body is empty, just use title
-            t.to_string()
+use futures_util::future::join_all;
+use notify_rust::{Notification, Timeout, Urgency};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::discord_sink::DiscordSink;
+use crate::image_cache::{self, ImageCache};
+use crate::push_sink::{GotifySink, NtfySink};
+use crate::settings::{
+    self, GroupingMode, GroupingSettings, ImageSettings, NotificationActionSettings,
+    NotificationUrgency, OfflineNotification, Settings, SinkConfig, SinkKind,
+};
+use crate::stream_state::EventKind;
+use crate::twitch_api::{Stream, User};
+use crate::webhook_sink::WebhookSink;
+use crate::AppMessage;
+
+const DEFAULT_ACTION_ID: &str = "default";
+const MUTE_ACTION_ID: &str = "mute-for-today";
+const CUSTOM_ACTION_PREFIX: &str = "action-";
+const EXPAND_ACTION_ID: &str = "show-all";
+
+/// A stream event that passed the streamer's filters, with everything a sink may need.
+#[derive(Debug, Clone)]
+pub struct StreamNotification {
+    pub kind: EventKind,
+    /// The stream as of the event; for `Offline` as it was last seen.
+    pub stream: Stream,
+    /// The streamer's alias, or their Twitch display name.
+    pub display_name: String,
+    /// Rendered from the event's template in `[templates]`.
+    pub summary: String,
+    pub body: String,
+    pub urgency: NotificationUrgency,
+    pub timeout_seconds: u32,
+    /// The streamer's configured icon name or path.
+    pub icon: Option<String>,
+    pub url: String,
+    pub avatar_url: Option<String>,
+    /// Thumbnail with the configured size filled in; absent for brand-new streams.
+    pub thumbnail_url: Option<String>,
+    /// The game before a `Game` change.
+    pub previous_game: Option<String>,
+    /// The title before a `Title` change.
+    pub previous_title: Option<String>,
+    /// How long the stream was down, for `BackOnline`.
+    pub offline_for: Option<Duration>,
+    /// The session that ended, for `Offline`.
+    pub session: Option<SessionSummary>,
+}
+
+#[cfg(test)]
+impl StreamNotification {
+    /// A go-live notification for tests.
+    pub fn sample() -> Self {
+        Self {
+            kind: EventKind::Live,
+            stream: Stream {
+                id: "40952121085".to_string(),
+                user_id: "1337".to_string(),
+                user_login: "cooler_user".to_string(),
+                user_name: "Cooler_User".to_string(),
+                game_id: "509658".to_string(),
+                game_name: "Just Chatting".to_string(),
+                title: "Morning stream".to_string(),
+                stream_type: "live".to_string(),
+                viewer_count: 42,
+                started_at: "2026-10-17T08:00:00Z".to_string(),
+                thumbnail_url: String::new(),
+            },
+            display_name: "Cooler_User".to_string(),
+            summary: "Cooler_User just went live!".to_string(),
+            body: "Playing: Just Chatting".to_string(),
+            urgency: NotificationUrgency::Critical,
+            timeout_seconds: 10,
+            icon: None,
+            url: "https://www.twitch.tv/cooler_user".to_string(),
+            avatar_url: Some("https://example.com/avatar.png".to_string()),
+            thumbnail_url: Some("https://example.com/thumb.jpg".to_string()),
+            previous_game: None,
+            previous_title: None,
+            offline_for: None,
+            session: None,
+        }
+    }
+}
+
+/// Summary of a finished stream session.
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub duration: Duration,
+    pub peak_viewers: u64,
+    pub games_played: Vec<String>,
+}
+
+/// Notifications from one check that are shown as a single summary.
+#[derive(Debug, Clone)]
+pub struct NotificationGroup {
+    /// E.g. "5 streamers went live".
+    pub summary: String,
+    /// The streamers and their games, e.g. "A (Game), B (Game) and 3 more".
+    pub body: String,
+    /// Whether to offer showing the notifications one by one.
+    pub expandable: bool,
+    pub notifications: Vec<StreamNotification>,
+}
+
+impl NotificationGroup {
+    pub fn new(notifications: Vec<StreamNotification>, settings: &GroupingSettings) -> Self {
+        let first_kind = notifications.first().map(|n| n.kind);
+        let same_kind = notifications.iter().all(|n| Some(n.kind) == first_kind);
+        let count = notifications.len();
+        let summary = match first_kind.filter(|_| same_kind) {
+            Some(EventKind::Live) => format!("{} streamers went live", count),
+            Some(EventKind::Game) => format!("{} streamers changed game", count),
+            Some(EventKind::Title) => format!("{} streamers changed title", count),
+            Some(EventKind::BackOnline) => format!("{} streamers are back online", count),
+            Some(EventKind::Offline) => format!("{} streamers went offline", count),
+            None => format!("{} stream updates", count),
+        };
+
+        let mut listed: Vec<String> = notifications
+            .iter()
+            .take(settings.max_listed)
+            .map(|n| match n.stream.game_name.as_str() {
+                "" => n.display_name.clone(),
+                game => format!("{} ({})", n.display_name, game),
+            })
+            .collect();
+        let rest = count.saturating_sub(listed.len());
+        let body = if rest > 0 {
+            format!("{} and {} more", listed.join(", "), rest)
+        } else if let Some(last) = listed.pop().filter(|_| !listed.is_empty()) {
+            format!("{} and {}", listed.join(", "), last)
         } else {
-            // Combine body and title
-            format!("{}\n{}", body_content, t) // Add newline between body and title
+            listed.join(", ")
+        };
+
+        Self {
+            summary,
+            body,
+            expandable: settings.mode == GroupingMode::Summary,
+            notifications,
+        }
+    }
+
+    /// Replaces the generated summary, e.g. for a digest.
+    pub fn with_summary(mut self, summary: String) -> Self {
+        self.summary = summary;
+        self
+    }
+}
+
+/// A notification backend. `send` is called on the monitor task and must not block:
+/// backends that do I/O hand the work off to a thread or a Tokio task.
+pub trait NotificationSink: Send {
+    fn name(&self) -> &'static str;
+
+    fn send(&self, notification: &StreamNotification);
+
+    /// Called instead of `send` for notifications that were grouped (see `[grouping]`).
+    /// Sinks without popups to spare the user from get them one by one.
+    fn send_group(&self, group: &NotificationGroup) {
+        for notification in &group.notifications {
+            self.send(notification);
         }
-    } else {
-        // If no title, use the original body content
-        body_content.to_string()
+    }
+
+    /// Called with the live streams after every poll.
+    fn observe_live_streams(&self, _streams: &[Stream]) {}
+
+    /// Called for every stream that ended, whether or not that was notified.
+    fn stream_ended(&self, _stream: &Stream) {}
+
+    /// Called once on shutdown. Returns the deliveries still in flight, which
+    /// shutdown waits for (up to `shutdown_timeout_seconds`).
+    fn shutdown(self: Box<Self>) -> Vec<JoinHandle<()>> {
+        Vec::new()
+    }
+}
+
+/// A configured sink and the events it receives.
+pub struct SinkEntry {
+    // The `[[sink]]` table it was built from
+    config: SinkConfig,
+    sink: Box<dyn NotificationSink>,
+}
+
+impl SinkEntry {
+    #[cfg(test)]
+    pub fn new(config: SinkConfig, sink: Box<dyn NotificationSink>) -> Self {
+        Self { config, sink }
+    }
+
+    pub fn sink(&self) -> &dyn NotificationSink {
+        self.sink.as_ref()
+    }
+
+    pub fn into_sink(self) -> Box<dyn NotificationSink> {
+        self.sink
+    }
+
+    pub fn accepts(&self, kind: EventKind) -> bool {
+        // None receives every event
+        self.config
+            .events
+            .as_ref()
+            .is_none_or(|events| events.contains(&kind))
+    }
+}
+
+/// Creates the sink for a `[[sink]]` table. Must be called from within the Tokio runtime.
+fn build_sink(
+    config: &SinkConfig,
+    settings: &Settings,
+    tx_app: &mpsc::Sender<AppMessage>,
+    users: &[User],
+) -> SinkEntry {
+    let sink: Box<dyn NotificationSink> = match &config.kind {
+        SinkKind::Desktop => Box::new(DesktopSink::new(settings, tx_app.clone(), users)),
+        SinkKind::Log => Box::new(LogSink),
+        SinkKind::Webhook(config) => Box::new(WebhookSink::new(config)),
+        SinkKind::Discord(config) => Box::new(DiscordSink::new(config)),
+        SinkKind::Ntfy(config) => Box::new(NtfySink::new(config)),
+        SinkKind::Gotify(config) => Box::new(GotifySink::new(config)),
     };
+    info!(
+        "(Monitor Task) Sending notifications to the {} sink",
+        sink.name()
+    );
+    SinkEntry {
+        config: config.clone(),
+        sink,
+    }
+}
+
+/// Creates the sinks from the `[[sink]]` tables.
+/// Must be called from within the Tokio runtime.
+pub fn build_sinks(
+    settings: &Settings,
+    tx_app: &mpsc::Sender<AppMessage>,
+    users: &[User],
+) -> Vec<SinkEntry> {
+    settings
+        .sinks
+        .iter()
+        .map(|config| build_sink(config, settings, tx_app, users))
+        .collect()
+}
+
+/// True if the global settings the desktop sink is built from differ.
+fn desktop_settings_changed(previous: &Settings, settings: &Settings) -> bool {
+    previous.images != settings.images
+        || previous.notification_actions != settings.notification_actions
+        || previous.replace_notifications != settings.replace_notifications
+        || previous.offline_notification != settings.offline_notification
+}
+
+/// The sinks for reloaded settings. Sinks whose configuration didn't change carry
+/// over with their state, such as the Discord messages to edit and the desktop
+/// notifications to replace. The others are shut down, and waited for up to
+/// `timeout`, before their replacements start, so that a webhook queue file is
+/// never worked on twice.
+pub async fn reload_sinks(
+    current: Vec<SinkEntry>,
+    previous: &Settings,
+    settings: &Settings,
+    tx_app: &mpsc::Sender<AppMessage>,
+    users: &[User],
+    timeout: Duration,
+) -> Vec<SinkEntry> {
+    let desktop_changed = desktop_settings_changed(previous, settings);
+    let mut current: Vec<Option<SinkEntry>> = current.into_iter().map(Some).collect();
+    let kept: Vec<Option<SinkEntry>> = settings
+        .sinks
+        .iter()
+        .map(|config| {
+            if desktop_changed && matches!(config.kind, SinkKind::Desktop) {
+                return None;
+            }
+            current
+                .iter_mut()
+                .find(|entry| entry.as_ref().is_some_and(|e| e.config == *config))
+                .and_then(Option::take)
+        })
+        .collect();
 
-    // Use app_name that matches your .desktop file if you create one later
-    match Notification::new()
-        .appname("twitch-notifier")
-        .summary(summary)
-        .body(&final_body) // Use the potentially modified body
-        // .icon("dialog-information") // Optional: specify an icon
-        .timeout(notify_rust::Timeout::Milliseconds(10000)) // Show for 10 seconds
-        .show()
+    let retired: Vec<_> = current
+        .into_iter()
+        .flatten()
+        .inspect(|entry| {
+            info!(
+                "(Monitor Task) Configuration of the {} sink changed, restarting it",
+                entry.sink().name()
+            )
+        })
+        .flat_map(|entry| entry.into_sink().shutdown())
+        .collect();
+    if !retired.is_empty()
+        && tokio::time::timeout(timeout, join_all(retired))
+            .await
+            .is_err()
     {
-        Ok(_) => {
-            info!("Sent notification: {}", summary);
+        warn!("(Monitor Task) Replaced sinks were still delivering, starting the new ones anyway.");
+    }
+
+    settings
+        .sinks
+        .iter()
+        .zip(kept)
+        .map(|(config, kept)| kept.unwrap_or_else(|| build_sink(config, settings, tx_app, users)))
+        .collect()
+}
+
+impl From<NotificationUrgency> for Urgency {
+    fn from(urgency: NotificationUrgency) -> Self {
+        match urgency {
+            NotificationUrgency::Low => Urgency::Low,
+            NotificationUrgency::Normal => Urgency::Normal,
+            NotificationUrgency::Critical => Urgency::Critical,
+        }
+    }
+}
+
+/// Writes notifications to the log, e.g. for machines without a desktop.
+pub struct LogSink;
+
+impl NotificationSink for LogSink {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    fn send(&self, n: &StreamNotification) {
+        let session = n.session.as_ref();
+        info!(
+            kind = ?n.kind,
+            streamer = %n.display_name,
+            game = %n.stream.game_name,
+            title = %n.stream.title,
+            viewers = n.stream.viewer_count,
+            url = %n.url,
+            thumbnail = n.thumbnail_url.as_deref().unwrap_or_default(),
+            previous_game = n.previous_game.as_deref().unwrap_or_default(),
+            duration_seconds = session.map(|s| s.duration.as_secs()),
+            peak_viewers = session.map(|s| s.peak_viewers),
+            games_played = session.map(|s| s.games_played.join(", ")),
+            "Notification: {}",
+            n.summary
+        );
+    }
+}
+
+/// Desktop notifications through the freedesktop notification service (libnotify).
+pub struct DesktopSink {
+    // None if there is no cache directory or images are disabled
+    images: Option<ImageCache>,
+    image_settings: ImageSettings,
+    action_settings: NotificationActionSettings,
+    // "Mute for today" is handled by the monitor task
+    tx_app: mpsc::Sender<AppMessage>,
+    replace_notifications: bool,
+    offline_notification: OfflineNotification,
+    shown: ShownNotifications,
+}
+
+impl DesktopSink {
+    pub fn new(settings: &Settings, tx_app: mpsc::Sender<AppMessage>, users: &[User]) -> Self {
+        let image_settings = settings.images.clone();
+        let images = if image_settings.avatars || image_settings.thumbnails {
+            let dir = image_settings
+                .cache_dir
+                .clone()
+                .or_else(settings::cache_dir);
+            if dir.is_none() {
+                warn!("(Monitor Task) No cache directory found, notifications won't have images.");
+            }
+            dir.map(ImageCache::new)
+        } else {
+            None
+        };
+
+        let sink = Self {
+            images,
+            image_settings,
+            action_settings: settings.notification_actions.clone(),
+            tx_app,
+            replace_notifications: settings.replace_notifications,
+            offline_notification: settings.offline_notification,
+            shown: ShownNotifications::default(),
+        };
+        // Warm the cache so the first notifications already have avatars
+        for user in users {
+            sink.avatar(&user.id, &user.profile_image_url);
+        }
+        sink
+    }
+
+    /// The cached profile image of a streamer, refreshed in the background when stale.
+    fn avatar(&self, user_id: &str, url: &str) -> Option<PathBuf> {
+        let images = self
+            .images
+            .as_ref()
+            .filter(|_| self.image_settings.avatars)?;
+        images.get(
+            &format!("avatar-{}", user_id),
+            url,
+            Duration::from_secs(self.image_settings.avatar_cache_seconds),
+        )
+    }
+
+    /// The cached thumbnail of a live stream, refreshed in the background when stale.
+    fn thumbnail(&self, stream: &Stream) -> Option<PathBuf> {
+        let images = self
+            .images
+            .as_ref()
+            .filter(|_| self.image_settings.thumbnails)?;
+        let settings = &self.image_settings;
+        images.get(
+            &format!("thumbnail-{}", stream.user_id),
+            &image_cache::thumbnail_url(
+                &stream.thumbnail_url,
+                settings.thumbnail_width,
+                settings.thumbnail_height,
+            ),
+            Duration::from_secs(settings.thumbnail_cache_seconds),
+        )
+    }
+
+    /// The desktop notification for a stream event, with its actions. Events after the
+    /// go-live replace the notification the stream already has on screen.
+    fn prepare(&self, n: &StreamNotification) -> PreparedNotification {
+        let avatar = n
+            .avatar_url
+            .as_deref()
+            .and_then(|url| self.avatar(&n.stream.user_id, url));
+        let thumbnail = match n.kind {
+            EventKind::Offline => None,
+            _ => self.thumbnail(&n.stream),
+        };
+
+        // Use app_name that matches your .desktop file if you create one later
+        let mut notification = Notification::new();
+        notification
+            .appname("twitch-notifier")
+            .summary(&n.summary)
+            .body(&n.body)
+            .urgency(n.urgency.into())
+            .timeout(Timeout::Milliseconds(
+                n.timeout_seconds.saturating_mul(1000),
+            ));
+        // A configured icon takes precedence over the avatar
+        let icon = n
+            .icon
+            .as_deref()
+            .or(avatar.as_deref().and_then(Path::to_str));
+        if let Some(icon) = icon {
+            notification.icon(icon);
+        }
+        if let Some(image) = thumbnail.as_deref().and_then(Path::to_str) {
+            notification.image_path(image);
+        }
+
+        let actions = NotificationActions {
+            stream: n.stream.clone(),
+            settings: self.action_settings.clone(),
+            tx_app: self.tx_app.clone(),
+        };
+        actions.add_to(&mut notification);
+
+        let user_id = &n.stream.user_id;
+        let closes_on_offline = self.offline_notification == OfflineNotification::Close;
+        let replaces = match n.kind {
+            // A new session always gets a new notification
+            EventKind::Live => None,
+            EventKind::Offline if closes_on_offline => None,
+            _ if !self.replace_notifications => None,
+            _ => self.shown.id(user_id),
+        };
+        if let Some(id) = replaces {
+            debug!(id, "Replacing the stream's notification");
+            notification.id(id);
+        }
+        PreparedNotification {
+            notification,
+            actions,
+            // `stream_ended` closes the session's notification, never this summary
+            tracking: match n.kind {
+                EventKind::Offline if closes_on_offline => None,
+                _ => Some(self.shown.track(user_id)),
+            },
+        }
+    }
+}
+
+impl NotificationSink for DesktopSink {
+    fn name(&self) -> &'static str {
+        "desktop"
+    }
+
+    fn send(&self, n: &StreamNotification) {
+        self.prepare(n).show();
+    }
+
+    /// Shows one summary; "Show all" (or clicking it) shows the notifications it stands for.
+    fn send_group(&self, group: &NotificationGroup) {
+        let notifications = &group.notifications;
+        let urgency = notifications
+            .iter()
+            .map(|n| n.urgency)
+            .max()
+            .unwrap_or_default();
+        let timeout_seconds = notifications
+            .iter()
+            .map(|n| n.timeout_seconds)
+            .max()
+            .unwrap_or_default();
+
+        let mut notification = Notification::new();
+        notification
+            .appname("twitch-notifier")
+            .summary(&group.summary)
+            .body(&group.body)
+            .urgency(urgency.into())
+            .timeout(Timeout::Milliseconds(timeout_seconds.saturating_mul(1000)));
+        // Built now, so the images are the ones the summary was sent with
+        let expanded: Vec<_> = if group.expandable {
+            // Clicking the summary expands it too; servers that list the default action
+            // as a button would otherwise show "Show all" twice
+            notification
+                .action(DEFAULT_ACTION_ID, "")
+                .action(EXPAND_ACTION_ID, "Show all");
+            notifications.iter().map(|n| self.prepare(n)).collect()
+        } else {
+            Vec::new()
+        };
+        show(notification, None, move |action_id| match action_id {
+            DEFAULT_ACTION_ID | EXPAND_ACTION_ID => {
+                for prepared in expanded {
+                    prepared.show();
+                }
+            }
+            _ => debug!("Grouped notification closed without expanding it"),
+        });
+    }
+
+    /// Keeps the thumbnails of live streams fresh, so they are ready for the next notification.
+    fn observe_live_streams(&self, streams: &[Stream]) {
+        for stream in streams {
+            self.thumbnail(stream);
+        }
+    }
+
+    fn stream_ended(&self, stream: &Stream) {
+        if self.offline_notification != OfflineNotification::Close {
+            return;
+        }
+        let Some(id) = self.shown.forget(&stream.user_id) else {
+            return;
+        };
+        // A D-Bus round trip; keep it off the monitor task
+        let spawned = std::thread::Builder::new()
+            .name("notification-close".to_string())
+            .spawn(move || {
+                if let Err(e) = close_notification(id) {
+                    warn!("Failed to close notification {}: {}", id, e);
+                }
+            });
+        if let Err(e) = spawned {
+            error!("Failed to start the notification closer: {}", e);
+        }
+    }
+}
+
+/// A desktop notification ready to be shown, with what happens when it's acted on.
+struct PreparedNotification {
+    notification: Notification,
+    actions: NotificationActions,
+    tracking: Option<Tracking>,
+}
+
+impl PreparedNotification {
+    fn show(self) {
+        let actions = self.actions;
+        show(self.notification, self.tracking, move |action_id| {
+            actions.invoke(action_id)
+        });
+    }
+}
+
+/// The desktop notification each stream has on screen, keyed by user ID, so later
+/// events of the same session can replace it.
+#[derive(Debug, Clone, Default)]
+struct ShownNotifications(Arc<Mutex<ShownState>>);
+
+#[derive(Debug, Default)]
+struct ShownState {
+    next_generation: u64,
+    // Notification ID, and the generation of the notification last shown with it
+    by_user: HashMap<String, (u32, u64)>,
+}
+
+impl ShownNotifications {
+    fn lock(&self) -> MutexGuard<'_, ShownState> {
+        self.0.lock().expect("shown notifications mutex poisoned")
+    }
+
+    fn id(&self, user_id: &str) -> Option<u32> {
+        self.lock().by_user.get(user_id).map(|(id, _)| *id)
+    }
+
+    fn track(&self, user_id: &str) -> Tracking {
+        let mut state = self.lock();
+        state.next_generation += 1;
+        Tracking {
+            shown: self.clone(),
+            user_id: user_id.to_string(),
+            generation: state.next_generation,
+        }
+    }
+
+    fn forget(&self, user_id: &str) -> Option<u32> {
+        self.lock().by_user.remove(user_id).map(|(id, _)| id)
+    }
+}
+
+/// Ties a notification to the stream it is about, once it's on screen.
+struct Tracking {
+    shown: ShownNotifications,
+    user_id: String,
+    generation: u64,
+}
+
+impl Tracking {
+    fn shown(&self, id: u32) {
+        self.shown
+            .lock()
+            .by_user
+            .insert(self.user_id.clone(), (id, self.generation));
+    }
+
+    /// False once a later notification replaced this one. The listeners of both
+    /// get the actions on the shared ID, and only the latest may act on them.
+    fn is_current(&self, id: u32) -> bool {
+        match self.shown.lock().by_user.get(&self.user_id) {
+            Some(&(shown_id, generation)) if shown_id == id => generation == self.generation,
+            _ => true,
+        }
+    }
+
+    /// Forgets the notification after it was closed or acted on.
+    fn closed(&self, id: u32) {
+        let mut state = self.shown.lock();
+        if state.by_user.get(&self.user_id) == Some(&(id, self.generation)) {
+            state.by_user.remove(&self.user_id);
+        }
+    }
+}
+
+/// Closes a notification by ID. The handle from showing it is owned by its
+/// listener thread, so this asks the notification service directly.
+fn close_notification(id: u32) -> zbus::Result<()> {
+    let connection = zbus::blocking::Connection::session()?;
+    connection.call_method(
+        Some("org.freedesktop.Notifications"),
+        "/org/freedesktop/Notifications",
+        Some("org.freedesktop.Notifications"),
+        "CloseNotification",
+        &id,
+    )?;
+    Ok(())
+}
+
+/// Shows a notification on its own thread, which then waits for the user to pick an
+/// action; nothing else is blocked meanwhile.
+fn show<F>(notification: Notification, tracking: Option<Tracking>, on_action: F)
+where
+    F: FnOnce(&str) + Send + 'static,
+{
+    let spawned = std::thread::Builder::new()
+        .name("notification-actions".to_string())
+        .spawn(move || match notification.show() {
+            Ok(handle) => {
+                info!("Sent notification: {}", notification.summary);
+                let id = handle.id();
+                if let Some(tracking) = &tracking {
+                    tracking.shown(id);
+                }
+                handle.wait_for_action(|action_id| {
+                    let current = tracking.as_ref().is_none_or(|t| t.is_current(id));
+                    if let Some(tracking) = &tracking {
+                        tracking.closed(id);
+                    }
+                    if current {
+                        on_action(action_id);
+                    } else {
+                        debug!(id, "Notification was replaced, ignoring its old actions");
+                    }
+                });
+            }
+            Err(e) => {
+                error!("Failed to send notification: {}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start the notification action listener: {}", e);
+    }
+}
+
+/// The actions offered on a notification, with the stream their commands are filled in from.
+#[derive(Debug, Clone)]
+struct NotificationActions {
+    stream: Stream,
+    settings: NotificationActionSettings,
+    tx_app: mpsc::Sender<AppMessage>,
+}
+
+impl NotificationActions {
+    fn add_to(&self, notification: &mut Notification) {
+        if !self.settings.default.trim().is_empty() {
+            notification.action(DEFAULT_ACTION_ID, "Open");
+        }
+        for (i, action) in self.settings.actions.iter().enumerate() {
+            notification.action(&format!("{}{}", CUSTOM_ACTION_PREFIX, i), &action.label);
+        }
+        if self.settings.mute_for_today {
+            notification.action(MUTE_ACTION_ID, "Mute for today");
+        }
+    }
+
+    /// Runs the action the user picked. Called on the listener thread, so it may block.
+    fn invoke(&self, action_id: &str) {
+        match action_id {
+            DEFAULT_ACTION_ID => run_command(&self.settings.default, &self.stream),
+            MUTE_ACTION_ID => {
+                let login = self.stream.user_login.clone();
+                if self
+                    .tx_app
+                    .blocking_send(AppMessage::MuteForToday { login })
+                    .is_err()
+                {
+                    warn!("Monitor task is gone, cannot mute streamer");
+                }
+            }
+            "__closed" => debug!("Notification closed without an action"),
+            other => {
+                let action = other
+                    .strip_prefix(CUSTOM_ACTION_PREFIX)
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .and_then(|i| self.settings.actions.get(i));
+                match action {
+                    Some(action) => run_command(&action.command, &self.stream),
+                    None => debug!(action_id = other, "Ignoring unknown notification action"),
+                }
+            }
+        }
+    }
+}
+
+/// Splits a command template on whitespace and fills in the stream's placeholders.
+/// Splitting first keeps a title with spaces in a single argument.
+fn expand_command(template: &str, stream: &Stream) -> Vec<String> {
+    let url = stream_url(&stream.user_login);
+    template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{url}", &url)
+                .replace("{login}", &stream.user_login)
+                .replace("{name}", &stream.user_name)
+                .replace("{user_id}", &stream.user_id)
+                .replace("{game}", &stream.game_name)
+                .replace("{title}", &stream.title)
+        })
+        .collect()
+}
+
+/// Launches an action command and waits for it to exit, so it doesn't linger as a zombie.
+fn run_command(template: &str, stream: &Stream) {
+    let args = expand_command(template, stream);
+    let Some((program, args)) = args.split_first() else {
+        return;
+    };
+    info!("Running notification action: {}", program);
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match child.and_then(|mut child| child.wait()) {
+        Ok(status) if !status.success() => {
+            warn!("Notification action '{}' exited with {}", program, status)
         }
-        Err(e) => {
-            error!("Failed to send notification: {}", e);
+        Ok(_) => {}
+        Err(e) => error!("Failed to run notification action '{}': {}", program, e),
+    }
+}
+
+#[cfg(feature = "tray")]
+/// Runs an action command for a stream outside of a notification, e.g. from the tray
+/// menu. It gets its own thread, as `run_command` waits for the command to exit.
+pub fn spawn_command(template: &str, stream: &Stream) {
+    let (template, stream) = (template.to_string(), stream.clone());
+    let spawned = std::thread::Builder::new()
+        .name("stream-action".to_string())
+        .spawn(move || run_command(&template, &stream));
+    if let Err(e) = spawned {
+        error!("Failed to start the stream action thread: {}", e);
+    }
+}
+
+/// The channel page of a streamer.
+pub fn stream_url(login: &str) -> String {
+    format!("https://twitch.tv/{}", login)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(name: &str, game: &str) -> StreamNotification {
+        let mut notification = StreamNotification::sample();
+        notification.display_name = name.to_string();
+        notification.stream.game_name = game.to_string();
+        notification
+    }
+
+    fn grouping(max_listed: usize) -> GroupingSettings {
+        GroupingSettings {
+            max_listed,
+            ..GroupingSettings::default()
         }
     }
+
+    #[test]
+    fn group_names_streamers_and_games() {
+        let group = NotificationGroup::new(
+            vec![
+                live("A", "Minecraft"),
+                live("B", "Just Chatting"),
+                live("C", ""),
+            ],
+            &grouping(5),
+        );
+        assert_eq!(group.summary, "3 streamers went live");
+        assert_eq!(group.body, "A (Minecraft), B (Just Chatting) and C");
+        assert!(group.expandable);
+    }
+
+    #[test]
+    fn group_lists_at_most_max_listed() {
+        let notifications = ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|name| live(name, "Minecraft"))
+            .collect();
+        let group = NotificationGroup::new(notifications, &grouping(2));
+        assert_eq!(group.summary, "5 streamers went live");
+        assert_eq!(group.body, "A (Minecraft), B (Minecraft) and 3 more");
+    }
+
+    #[test]
+    fn group_of_mixed_events_is_stream_updates() {
+        let mut offline = live("B", "Minecraft");
+        offline.kind = EventKind::Offline;
+        let settings = GroupingSettings {
+            mode: GroupingMode::SummaryOnly,
+            ..grouping(5)
+        };
+        let group = NotificationGroup::new(vec![live("A", "Minecraft"), offline], &settings);
+        assert_eq!(group.summary, "2 stream updates");
+        assert_eq!(group.body, "A (Minecraft) and B (Minecraft)");
+        assert!(!group.expandable);
+    }
+
+    #[test]
+    fn command_placeholders_fill_whole_arguments() {
+        let mut stream = StreamNotification::sample().stream;
+        stream.title = "Speedrun; rm -rf ~ && echo $HOME \"quoted\" | tee".to_string();
+        let args = expand_command("xdg-open {url} --title={title} {name}", &stream);
+        assert_eq!(
+            args,
+            [
+                "xdg-open",
+                "https://twitch.tv/cooler_user",
+                "--title=Speedrun; rm -rf ~ && echo $HOME \"quoted\" | tee",
+                "Cooler_User",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_command_placeholders_are_left_alone() {
+        let stream = StreamNotification::sample().stream;
+        let args = expand_command("notify {login} {viewers} {user_id}{}", &stream);
+        assert_eq!(args, ["notify", "cooler_user", "{viewers}", "1337{}"]);
+    }
 }
@@ -0,0 +1,402 @@
+// src/push_sink.rs
+
+use reqwest::{RequestBuilder, StatusCode, Url};
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+
+use crate::notifications::{NotificationSink, StreamNotification};
+use crate::settings::{GotifySinkConfig, NotificationUrgency, NtfySinkConfig};
+use crate::stream_state::EventKind;
+use crate::templates::{self, Escape};
+
+const MAX_ATTEMPTS: u32 = 3;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Checks the settings shared by the push sinks when the config is loaded.
+fn validate_common(server: &str, title: Option<&str>, message: Option<&str>) -> Result<(), String> {
+    Url::parse(server).map_err(|e| format!("invalid server URL '{}': {}", server, e))?;
+    for template in [title, message].into_iter().flatten() {
+        templates::validate(template, Escape::None, true)?;
+    }
+    Ok(())
+}
+
+pub fn validate_ntfy(config: &NtfySinkConfig) -> Result<(), String> {
+    validate_common(
+        &config.server,
+        config.title.as_deref(),
+        config.message.as_deref(),
+    )
+    .map_err(|e| format!("ntfy sink: {}", e))?;
+    if config.topic.is_empty() {
+        return Err("ntfy sink needs a topic".to_string());
+    }
+    if let Some(priority) = config.priorities.values().find(|p| !(1..=5).contains(*p)) {
+        return Err(format!("ntfy priority {} is not between 1 and 5", priority));
+    }
+    Ok(())
+}
+
+pub fn validate_gotify(config: &GotifySinkConfig) -> Result<(), String> {
+    validate_common(
+        &config.server,
+        config.title.as_deref(),
+        config.message.as_deref(),
+    )
+    .map_err(|e| format!("Gotify sink: {}", e))?;
+    if config.token.is_empty() {
+        return Err("Gotify sink needs an application token".to_string());
+    }
+    if let Some(priority) = config.priorities.values().find(|p| **p > 10) {
+        return Err(format!(
+            "Gotify priority {} is not between 0 and 10",
+            priority
+        ));
+    }
+    Ok(())
+}
+
+fn render_or(template: Option<&str>, n: &StreamNotification, fallback: &str) -> String {
+    template
+        .map(|t| templates::render(t, n, Escape::None))
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// The publish tasks of a sink that haven't finished, so shutdown can wait for them.
+#[derive(Debug, Default)]
+struct InFlight(Mutex<Vec<JoinHandle<()>>>);
+
+impl InFlight {
+    fn push(&self, handle: JoinHandle<()>) {
+        let mut handles = self.0.lock().expect("in-flight mutex poisoned");
+        handles.retain(|h| !h.is_finished());
+        handles.push(handle);
+    }
+
+    fn take(&self) -> Vec<JoinHandle<()>> {
+        std::mem::take(&mut *self.0.lock().expect("in-flight mutex poisoned"))
+    }
+}
+
+/// Sends a request in the background, retrying on rate limits, server errors
+/// and network errors. `build` creates a fresh request for every attempt.
+fn publish<F>(service: &'static str, in_flight: &InFlight, build: F)
+where
+    F: Fn() -> RequestBuilder + Send + 'static,
+{
+    in_flight.push(tokio::spawn(async move {
+        let mut delay = INITIAL_RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match build().timeout(REQUEST_TIMEOUT).send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("({}) Published notification", service);
+                    return;
+                }
+                Ok(response)
+                    if response.status().is_server_error()
+                        || response.status() == StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    warn!(attempt, status = %response.status(), "({}) Server unavailable", service);
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let message = response.text().await.unwrap_or_default();
+                    error!(status = %status, "({}) Notification rejected: {}", service, message);
+                    return;
+                }
+                Err(e) => warn!(attempt, "({}) Publishing failed: {}", service, e),
+            }
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+        error!("({}) Giving up after {} attempts", service, MAX_ATTEMPTS);
+    }));
+}
+
+/// Publishes to an ntfy topic, e.g. on ntfy.sh or a self-hosted server.
+pub struct NtfySink {
+    client: reqwest::Client,
+    config: NtfySinkConfig,
+    in_flight: InFlight,
+}
+
+impl NtfySink {
+    pub fn new(config: &NtfySinkConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config: config.clone(),
+            in_flight: InFlight::default(),
+        }
+    }
+
+    /// ntfy priorities run from 1 (min) to 5 (max).
+    fn priority(&self, n: &StreamNotification) -> u8 {
+        self.config
+            .priorities
+            .get(&n.kind)
+            .copied()
+            .unwrap_or(match n.urgency {
+                NotificationUrgency::Low => 2,
+                NotificationUrgency::Normal => 3,
+                NotificationUrgency::Critical => 5,
+            })
+    }
+}
+
+/// Emoji shortcodes ntfy shows in front of the title.
+fn ntfy_tag(kind: EventKind) -> &'static str {
+    match kind {
+        EventKind::Live => "red_circle",
+        EventKind::Game => "video_game",
+        EventKind::Title => "pencil2",
+        EventKind::BackOnline => "arrows_counterclockwise",
+        EventKind::Offline => "zzz",
+    }
+}
+
+impl NotificationSink for NtfySink {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    fn send(&self, n: &StreamNotification) {
+        let mut tags = vec![ntfy_tag(n.kind).to_string()];
+        tags.extend(self.config.tags.iter().cloned());
+        let mut body = json!({
+            "topic": self.config.topic,
+            "title": render_or(self.config.title.as_deref(), n, &n.summary),
+            "message": render_or(self.config.message.as_deref(), n, &n.body),
+            "priority": self.priority(n),
+            "tags": tags,
+            "click": n.url,
+        });
+        if let Some(avatar_url) = &n.avatar_url {
+            body["icon"] = json!(avatar_url);
+        }
+        if let Some(thumbnail_url) = n
+            .thumbnail_url
+            .as_ref()
+            .filter(|_| self.config.attach_thumbnail)
+        {
+            body["attach"] = json!(thumbnail_url);
+        }
+
+        // Publishing as JSON goes to the server root, with the topic in the body
+        let client = self.client.clone();
+        let url = self.config.server.clone();
+        let token = self.config.token.clone();
+        publish("ntfy", &self.in_flight, move || {
+            let request = client.post(&url).json(&body);
+            match &token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        });
+    }
+
+    fn shutdown(self: Box<Self>) -> Vec<JoinHandle<()>> {
+        self.in_flight.take()
+    }
+}
+
+/// Publishes to a Gotify server as an application.
+pub struct GotifySink {
+    client: reqwest::Client,
+    config: GotifySinkConfig,
+    in_flight: InFlight,
+}
+
+impl GotifySink {
+    pub fn new(config: &GotifySinkConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config: config.clone(),
+            in_flight: InFlight::default(),
+        }
+    }
+
+    /// Gotify priorities run from 0 to 10; most clients only pop up from 4 up.
+    fn priority(&self, n: &StreamNotification) -> u8 {
+        self.config
+            .priorities
+            .get(&n.kind)
+            .copied()
+            .unwrap_or(match n.urgency {
+                NotificationUrgency::Low => 2,
+                NotificationUrgency::Normal => 5,
+                NotificationUrgency::Critical => 8,
+            })
+    }
+}
+
+impl NotificationSink for GotifySink {
+    fn name(&self) -> &'static str {
+        "gotify"
+    }
+
+    fn send(&self, n: &StreamNotification) {
+        let body: Value = json!({
+            "title": render_or(self.config.title.as_deref(), n, &n.summary),
+            "message": render_or(self.config.message.as_deref(), n, &n.body),
+            "priority": self.priority(n),
+            "extras": {
+                "client::display": { "contentType": "text/plain" },
+                "client::notification": { "click": { "url": n.url } },
+            },
+        });
+
+        let client = self.client.clone();
+        let url = format!("{}/message", self.config.server.trim_end_matches('/'));
+        let token = self.config.token.clone();
+        publish("Gotify", &self.in_flight, move || {
+            client.post(&url).header("X-Gotify-Key", &token).json(&body)
+        });
+    }
+
+    fn shutdown(self: Box<Self>) -> Vec<JoinHandle<()>> {
+        self.in_flight.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, Uri};
+    use axum::Router;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// A request the test server received.
+    struct Received {
+        path: String,
+        headers: HeaderMap,
+        body: Value,
+    }
+
+    type Requests = Arc<Mutex<Vec<Received>>>;
+
+    /// Serves on a free local port and records every request, returning the server URL.
+    async fn start_server() -> (Requests, String) {
+        let requests = Requests::default();
+        let router =
+            Router::new()
+                .fallback(
+                    |State(requests): State<Requests>,
+                     uri: Uri,
+                     headers: HeaderMap,
+                     body: String| async move {
+                        requests.lock().unwrap().push(Received {
+                            path: uri.path().to_string(),
+                            headers,
+                            body: serde_json::from_str(&body).unwrap_or(Value::Null),
+                        });
+                        "{}"
+                    },
+                )
+                .with_state(requests.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (requests, url)
+    }
+
+    /// Sends one notification and waits until the sink has published it.
+    async fn publish_one(sink: Box<dyn NotificationSink>, requests: &Requests) -> Received {
+        sink.send(&StreamNotification::sample());
+        for handle in sink.shutdown() {
+            handle.await.unwrap();
+        }
+        let mut requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        requests.pop().unwrap()
+    }
+
+    #[tokio::test]
+    async fn ntfy_publishes_json_to_server_root() {
+        let (requests, server) = start_server().await;
+        let sink = NtfySink::new(&NtfySinkConfig {
+            server,
+            topic: "streams".to_string(),
+            token: Some("tk_secret".to_string()),
+            title: Some("{name} is live".to_string()),
+            message: None,
+            priorities: HashMap::new(),
+            tags: vec!["twitch".to_string()],
+            attach_thumbnail: true,
+        });
+
+        let request = publish_one(Box::new(sink), &requests).await;
+        assert_eq!(request.path, "/");
+        assert_eq!(request.headers["authorization"], "Bearer tk_secret");
+        assert_eq!(
+            request.body,
+            json!({
+                "topic": "streams",
+                "title": "Cooler_User is live",
+                "message": "Playing: Just Chatting",
+                "priority": 5,
+                "tags": ["red_circle", "twitch"],
+                "click": "https://www.twitch.tv/cooler_user",
+                "icon": "https://example.com/avatar.png",
+                "attach": "https://example.com/thumb.jpg",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn ntfy_attaches_thumbnail_only_when_enabled() {
+        let (requests, server) = start_server().await;
+        let sink = NtfySink::new(&NtfySinkConfig {
+            server,
+            topic: "streams".to_string(),
+            token: None,
+            title: None,
+            message: None,
+            priorities: HashMap::from([(EventKind::Live, 4)]),
+            tags: Vec::new(),
+            attach_thumbnail: false,
+        });
+
+        let request = publish_one(Box::new(sink), &requests).await;
+        assert!(request.headers.get("authorization").is_none());
+        assert_eq!(request.body["title"], "Cooler_User just went live!");
+        assert_eq!(request.body["priority"], 4);
+        assert!(request.body.get("attach").is_none());
+    }
+
+    #[tokio::test]
+    async fn gotify_posts_message_with_app_token() {
+        let (requests, server) = start_server().await;
+        let sink = GotifySink::new(&GotifySinkConfig {
+            server: format!("{}/", server),
+            token: "A_gotify_token".to_string(),
+            title: None,
+            message: Some("{title} ({game})".to_string()),
+            priorities: HashMap::new(),
+        });
+
+        let request = publish_one(Box::new(sink), &requests).await;
+        assert_eq!(request.path, "/message");
+        assert_eq!(request.headers["x-gotify-key"], "A_gotify_token");
+        assert_eq!(
+            request.body,
+            json!({
+                "title": "Cooler_User just went live!",
+                "message": "Morning stream (Just Chatting)",
+                "priority": 8,
+                "extras": {
+                    "client::display": { "contentType": "text/plain" },
+                    "client::notification": { "click": { "url": "https://www.twitch.tv/cooler_user" } },
+                },
+            })
+        );
+    }
+}
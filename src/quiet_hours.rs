@@ -0,0 +1,378 @@
+// src/quiet_hours.rs
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer};
+use std::collections::VecDeque;
+use tracing::warn;
+
+use crate::notifications::StreamNotification;
+
+/// Notifications held for a digest beyond this many drop the oldest, so a long
+/// quiet window with many busy streamers can't grow the queue without bound.
+pub const DIGEST_CAPACITY: usize = 100;
+
+/// The current time. Quiet hours are evaluated against a `Clock` so the schedule
+/// logic can be driven by any instant, not just the system time.
+pub trait Clock: Send {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub struct FixedClock(std::sync::Arc<std::sync::Mutex<DateTime<Utc>>>);
+
+#[cfg(test)]
+impl FixedClock {
+    /// A clock at an RFC 3339 time.
+    pub fn at(time: &str) -> Self {
+        Self(std::sync::Arc::new(std::sync::Mutex::new(utc(time))))
+    }
+
+    pub fn set(&self, time: &str) {
+        *self.0.lock().expect("clock mutex poisoned") = utc(time);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().expect("clock mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+pub fn utc(time: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(time)
+        .expect("valid RFC 3339 time")
+        .to_utc()
+}
+
+/// What happens to notifications during quiet hours. When several rules apply at
+/// once, the strictest one wins (the last one here).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietAction {
+    /// Sent right away, with low urgency.
+    LowUrgency,
+    /// Held back and sent as one digest when the quiet hours end.
+    Digest,
+    /// Dropped.
+    #[default]
+    Suppress,
+}
+
+/// The timezone a rule's times are in.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Timezone {
+    /// The system timezone, daylight saving time included.
+    #[default]
+    Local,
+    Fixed(FixedOffset),
+    /// An IANA zone such as "Europe/Berlin", daylight saving time included.
+    Named(Tz),
+}
+
+impl Timezone {
+    fn local_time(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Timezone::Local => now.with_timezone(&Local).naive_local(),
+            Timezone::Fixed(offset) => now.with_timezone(offset).naive_local(),
+            Timezone::Named(tz) => now.with_timezone(tz).naive_local(),
+        }
+    }
+}
+
+/// A quiet-hours rule: a daily time window during which notifications are held
+/// back, optionally only on some days of the week. Windows may wrap past
+/// midnight, e.g. 23:00-07:00; `days` are the days a window starts on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: NaiveTime,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: NaiveTime,
+    // Empty means every day
+    #[serde(default, deserialize_with = "deserialize_days")]
+    pub days: Vec<Weekday>,
+    #[serde(default)]
+    pub action: QuietAction,
+    #[serde(default, deserialize_with = "deserialize_timezone")]
+    pub timezone: Timezone,
+}
+
+impl QuietHours {
+    fn on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// True if `at` (in the rule's timezone) falls inside the window
+    /// (start inclusive, end exclusive).
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        let (time, day) = (at.time(), at.weekday());
+        if self.start <= self.end {
+            self.on(day) && self.start <= time && time < self.end
+        } else {
+            // The early-morning part belongs to the window that started the day before
+            (time >= self.start && self.on(day)) || (time < self.end && self.on(day.pred()))
+        }
+    }
+
+    /// True if the window covers the instant `now`.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.contains(self.timezone.local_time(now))
+    }
+}
+
+/// The quiet-hours rules of the global settings or of a streamer. In the config
+/// this is a single table or a list of them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "OneOrMany")]
+pub struct QuietSchedule {
+    pub rules: Vec<QuietHours>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(QuietHours),
+    Many(Vec<QuietHours>),
+}
+
+impl From<OneOrMany> for QuietSchedule {
+    fn from(value: OneOrMany) -> Self {
+        let rules = match value {
+            OneOrMany::One(rule) => vec![rule],
+            OneOrMany::Many(rules) => rules,
+        };
+        Self { rules }
+    }
+}
+
+impl QuietSchedule {
+    /// The strictest action of the rules active at `now`, if any.
+    pub fn action_at(&self, now: DateTime<Utc>) -> Option<QuietAction> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.is_active(now))
+            .map(|rule| rule.action)
+            .max()
+    }
+}
+
+/// Notifications held back by quiet hours until their streamer's window ends,
+/// oldest first.
+#[derive(Debug, Default)]
+pub struct Digest {
+    held: VecDeque<StreamNotification>,
+}
+
+impl Digest {
+    pub fn hold(&mut self, notification: StreamNotification) {
+        if self.held.len() >= DIGEST_CAPACITY {
+            warn!(
+                "(Monitor Task) More than {} notifications held for the digest, dropping the oldest",
+                DIGEST_CAPACITY
+            );
+            self.held.pop_front();
+        }
+        self.held.push_back(notification);
+    }
+
+    /// Removes and returns the held notifications whose quiet hours are over,
+    /// keeping those for which `still_quiet` is true.
+    pub fn take_due(
+        &mut self,
+        still_quiet: impl Fn(&StreamNotification) -> bool,
+    ) -> Vec<StreamNotification> {
+        let (held, due) = std::mem::take(&mut self.held)
+            .into_iter()
+            .partition(|n| still_quiet(n));
+        self.held = held;
+        due.into()
+    }
+}
+
+/// Parses "HH:MM" (or "HH:MM:SS") from the config file.
+fn deserialize_time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&raw, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(&raw, "%H:%M:%S"))
+        .map_err(|_| serde::de::Error::custom(format!("invalid time '{}', expected HH:MM", raw)))
+}
+
+/// Parses day names such as "mon" or "Monday".
+fn deserialize_days<'de, D>(deserializer: D) -> Result<Vec<Weekday>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|raw| {
+            raw.parse::<Weekday>()
+                .map_err(|_| serde::de::Error::custom(format!("invalid day '{}'", raw)))
+        })
+        .collect()
+}
+
+/// Parses "local", "UTC", a fixed offset such as "+02:00" or an IANA zone name
+/// such as "Europe/Berlin".
+fn deserialize_timezone<'de, D>(deserializer: D) -> Result<Timezone, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = String::deserialize(deserializer)?;
+    match raw.to_ascii_lowercase().as_str() {
+        "local" => Ok(Timezone::Local),
+        "utc" | "z" => Ok(Timezone::Fixed(
+            FixedOffset::east_opt(0).expect("zero offset is valid"),
+        )),
+        _ => raw
+            .parse::<FixedOffset>()
+            .map(Timezone::Fixed)
+            .or_else(|_| raw.parse::<Tz>().map(Timezone::Named))
+            .map_err(|_| {
+                serde::de::Error::custom(format!(
+                    "invalid timezone '{}', expected \"local\", \"UTC\", an offset like \"+02:00\" or a name like \"Europe/Berlin\"",
+                    raw
+                ))
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{GameFilter, TitleFilter};
+    use crate::settings::{NotificationUrgency, StreamerProfile};
+    use serde_json::json;
+
+    fn rule(config: serde_json::Value) -> QuietHours {
+        serde_json::from_value(config).unwrap()
+    }
+
+    fn schedule(config: serde_json::Value) -> QuietSchedule {
+        serde_json::from_value(config).unwrap()
+    }
+
+    fn profile(quiet_hours: &QuietSchedule, bypass_quiet_hours: bool) -> StreamerProfile<'_> {
+        static TITLE_FILTER: std::sync::LazyLock<TitleFilter> =
+            std::sync::LazyLock::new(TitleFilter::default);
+        static GAME_FILTER: std::sync::LazyLock<GameFilter> =
+            std::sync::LazyLock::new(GameFilter::default);
+        StreamerProfile {
+            alias: None,
+            icon: None,
+            urgency: NotificationUrgency::Normal,
+            timeout_seconds: 10,
+            back_online_urgency: NotificationUrgency::Low,
+            back_online_timeout_seconds: 5,
+            notify: &[],
+            title_filter: &TITLE_FILTER,
+            game_filter: &GAME_FILTER,
+            quiet_hours,
+            bypass_quiet_hours,
+            muted: false,
+        }
+    }
+
+    #[test]
+    fn window_wraps_past_midnight() {
+        let night = rule(json!({ "start": "23:00", "end": "07:00", "timezone": "UTC" }));
+        // 2026-10-16 is a Friday
+        assert!(!night.is_active(utc("2026-10-16T22:59:00Z")));
+        assert!(night.is_active(utc("2026-10-16T23:00:00Z")));
+        assert!(night.is_active(utc("2026-10-17T03:00:00Z")));
+        assert!(night.is_active(utc("2026-10-17T06:59:59Z")));
+        assert!(!night.is_active(utc("2026-10-17T07:00:00Z")));
+        assert!(!night.is_active(utc("2026-10-17T12:00:00Z")));
+    }
+
+    #[test]
+    fn days_are_the_days_a_window_starts_on() {
+        let friday_night = rule(json!({
+            "start": "22:00", "end": "02:00", "days": ["fri"], "timezone": "UTC"
+        }));
+        assert!(friday_night.is_active(utc("2026-10-16T23:00:00Z"))); // Fri
+        assert!(friday_night.is_active(utc("2026-10-17T01:00:00Z"))); // Sat, started Fri
+        assert!(!friday_night.is_active(utc("2026-10-17T23:00:00Z"))); // Sat
+        assert!(!friday_night.is_active(utc("2026-10-16T01:00:00Z"))); // Fri, started Thu
+
+        let office = rule(json!({
+            "start": "09:00", "end": "17:00", "days": ["Monday", "tue"], "timezone": "UTC"
+        }));
+        assert!(office.is_active(utc("2026-10-19T10:00:00Z"))); // Mon
+        assert!(office.is_active(utc("2026-10-20T16:59:00Z"))); // Tue
+        assert!(!office.is_active(utc("2026-10-21T10:00:00Z"))); // Wed
+    }
+
+    #[test]
+    fn named_timezones_follow_daylight_saving_time() {
+        let night = rule(json!({
+            "start": "22:00", "end": "06:00", "timezone": "Europe/Berlin"
+        }));
+        // 20:30 UTC is 22:30 in summer (CEST) but 21:30 in winter (CET)
+        assert!(night.is_active(utc("2026-07-01T20:30:00Z")));
+        assert!(!night.is_active(utc("2026-01-15T20:30:00Z")));
+        assert!(night.is_active(utc("2026-01-15T21:30:00Z")));
+
+        let offset = rule(json!({ "start": "22:00", "end": "06:00", "timezone": "+02:00" }));
+        assert!(offset.is_active(utc("2026-01-15T20:30:00Z")));
+
+        let invalid = serde_json::from_value::<QuietHours>(json!({
+            "start": "22:00", "end": "06:00", "timezone": "Mars/Olympus_Mons"
+        }));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn strictest_action_wins() {
+        let rules = schedule(json!([
+            { "start": "20:00", "end": "23:00", "action": "low_urgency", "timezone": "UTC" },
+            { "start": "22:00", "end": "07:00", "action": "digest", "timezone": "UTC" },
+        ]));
+        let at = |time| rules.action_at(utc(time));
+        assert_eq!(at("2026-10-16T21:00:00Z"), Some(QuietAction::LowUrgency));
+        assert_eq!(at("2026-10-16T22:30:00Z"), Some(QuietAction::Digest));
+        assert_eq!(at("2026-10-17T12:00:00Z"), None);
+    }
+
+    #[test]
+    fn bypass_ignores_quiet_hours() {
+        let rules = schedule(json!({ "start": "23:00", "end": "07:00", "timezone": "UTC" }));
+        let night = utc("2026-10-17T01:00:00Z");
+        assert_eq!(
+            profile(&rules, false).quiet_action(night),
+            Some(QuietAction::Suppress)
+        );
+        assert_eq!(profile(&rules, true).quiet_action(night), None);
+    }
+
+    #[test]
+    fn digest_drops_the_oldest_beyond_capacity() {
+        let mut digest = Digest::default();
+        for i in 0..DIGEST_CAPACITY + 5 {
+            let mut notification = StreamNotification::sample();
+            notification.summary = i.to_string();
+            digest.hold(notification);
+        }
+        let due = digest.take_due(|_| false);
+        assert_eq!(due.len(), DIGEST_CAPACITY);
+        assert_eq!(due[0].summary, "5");
+    }
+}
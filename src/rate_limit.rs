@@ -0,0 +1,325 @@
+// src/rate_limit.rs
+
+use reqwest::header::HeaderMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Once fewer than this share of the bucket's points remain, requests are spread
+/// out over the time left until the bucket refills instead of sent immediately.
+const LOW_BUDGET_FRACTION: f64 = 0.1;
+
+/// How long to back off after a 429 that carries no usable `Ratelimit-Reset` header.
+const FALLBACK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Snapshot of the Helix rate-limit bucket as last reported by Twitch.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitBudget {
+    /// Size of the bucket (`Ratelimit-Limit`).
+    pub limit: u32,
+    /// Points left in the bucket (`Ratelimit-Remaining`), minus requests sent since.
+    pub remaining: u32,
+    /// When the bucket is full again (`Ratelimit-Reset`).
+    pub reset_at: Instant,
+}
+
+impl RateLimitBudget {
+    /// True when the remaining points have dropped below `LOW_BUDGET_FRACTION` of the bucket.
+    pub fn is_low(&self) -> bool {
+        (self.remaining as f64) < (self.limit as f64) * LOW_BUDGET_FRACTION
+    }
+}
+
+/// Token-bucket scheduler for Helix requests, driven by the `Ratelimit-*` response headers.
+/// Until Twitch has reported a budget, requests pass through untouched.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    budget: Mutex<Option<RateLimitBudget>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current budget, if Twitch has reported one yet.
+    pub fn budget(&self) -> Option<RateLimitBudget> {
+        let mut budget = self.budget.lock().expect("rate limiter mutex poisoned");
+        Self::refill_if_reset(&mut budget);
+        *budget
+    }
+
+    /// Waits until a request may be sent and reserves one point for it.
+    pub async fn acquire(&self) {
+        loop {
+            let (delay, reserved) = {
+                let mut guard = self.budget.lock().expect("rate limiter mutex poisoned");
+                Self::refill_if_reset(&mut guard);
+                let Some(budget) = guard.as_mut() else {
+                    return; // No budget reported yet
+                };
+
+                let until_reset = budget.reset_at.saturating_duration_since(Instant::now());
+                if budget.remaining == 0 {
+                    if until_reset.is_zero() {
+                        return; // Nothing to wait for, let Twitch decide
+                    }
+                    (until_reset, false)
+                } else {
+                    budget.remaining -= 1;
+                    if budget.is_low() {
+                        // Spread what is left evenly over the rest of the window
+                        (until_reset / (budget.remaining + 1), true)
+                    } else {
+                        (Duration::ZERO, true)
+                    }
+                }
+            };
+
+            if !delay.is_zero() {
+                debug!(
+                    delay_ms = delay.as_millis() as u64,
+                    "Rate limit budget low, delaying request"
+                );
+                tokio::time::sleep(delay).await;
+            }
+            if reserved {
+                return;
+            }
+        }
+    }
+
+    /// Updates the bucket from the `Ratelimit-*` headers of a Helix response.
+    pub fn update_from_headers(&self, headers: &HeaderMap) {
+        let (Some(limit), Some(remaining), Some(reset)) = (
+            header_u64(headers, "ratelimit-limit"),
+            header_u64(headers, "ratelimit-remaining"),
+            header_u64(headers, "ratelimit-reset"),
+        ) else {
+            return;
+        };
+
+        let budget = RateLimitBudget {
+            limit: limit as u32,
+            remaining: remaining as u32,
+            reset_at: instant_from_unix(reset),
+        };
+        debug!(
+            limit = budget.limit,
+            remaining = budget.remaining,
+            "Updated rate limit budget"
+        );
+        *self.budget.lock().expect("rate limiter mutex poisoned") = Some(budget);
+    }
+
+    /// Records a 429 response and returns how long to wait before retrying.
+    /// Only for responses to App Access Token requests, whose bucket this tracks.
+    pub fn record_throttled(&self, headers: &HeaderMap) -> Duration {
+        self.update_from_headers(headers);
+
+        let mut guard = self.budget.lock().expect("rate limiter mutex poisoned");
+        let delay = match guard.as_mut() {
+            Some(budget) => {
+                budget.remaining = 0;
+                budget.reset_at.saturating_duration_since(Instant::now())
+            }
+            None => FALLBACK_RETRY_DELAY,
+        }
+        .max(FALLBACK_RETRY_DELAY);
+        warn!(
+            retry_in_ms = delay.as_millis() as u64,
+            "Twitch API rate limit exceeded"
+        );
+        delay
+    }
+
+    /// Treats the bucket as full again once its reset time has passed.
+    fn refill_if_reset(budget: &mut Option<RateLimitBudget>) {
+        if let Some(b) = budget.as_mut() {
+            if Instant::now() >= b.reset_at && b.remaining < b.limit {
+                b.remaining = b.limit;
+            }
+        }
+    }
+}
+
+/// How long to wait after a 429 from a bucket this limiter doesn't track, such as
+/// a User Access Token's.
+pub fn retry_delay(headers: &HeaderMap) -> Duration {
+    header_u64(headers, "ratelimit-reset")
+        .map(|reset| instant_from_unix(reset).saturating_duration_since(Instant::now()))
+        .unwrap_or_default()
+        .max(FALLBACK_RETRY_DELAY)
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Converts a unix timestamp (seconds) from a header into a monotonic `Instant`.
+fn instant_from_unix(secs: u64) -> Instant {
+    let target = UNIX_EPOCH + Duration::from_secs(secs);
+    let until = target
+        .duration_since(SystemTime::now())
+        .unwrap_or(Duration::ZERO);
+    Instant::now() + until
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unix_now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn headers(limit: u32, remaining: u32, reset: u64) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("Ratelimit-Limit", limit.into());
+        headers.insert("Ratelimit-Remaining", remaining.into());
+        headers.insert("Ratelimit-Reset", reset.into());
+        headers
+    }
+
+    /// Asserts `duration` is within a second below `expected`; header resets are whole
+    /// unix seconds, so part of the first one may already have passed.
+    fn assert_about(duration: Duration, expected: Duration) {
+        assert!(
+            duration <= expected && duration + Duration::from_secs(1) >= expected,
+            "{:?} is not about {:?}",
+            duration,
+            expected
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_pass_until_a_budget_is_reported() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+
+        limiter.acquire().await;
+
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(limiter.budget().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn headers_update_the_budget() {
+        let limiter = RateLimiter::new();
+        limiter.update_from_headers(&headers(800, 799, unix_now() + 60));
+
+        let budget = limiter.budget().unwrap();
+        assert_eq!((budget.limit, budget.remaining), (800, 799));
+        assert_about(budget.reset_at - Instant::now(), Duration::from_secs(60));
+        assert!(!budget.is_low());
+
+        // Responses without the full set of headers leave it alone
+        let mut partial = headers(800, 10, unix_now() + 60);
+        partial.remove("Ratelimit-Reset");
+        limiter.update_from_headers(&partial);
+        assert_eq!(limiter.budget().unwrap().remaining, 799);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_reserves_a_point_without_waiting() {
+        let limiter = RateLimiter::new();
+        limiter.update_from_headers(&headers(800, 500, unix_now() + 60));
+        let start = Instant::now();
+
+        limiter.acquire().await;
+
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(limiter.budget().unwrap().remaining, 499);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_blocks_when_empty_and_resumes_at_reset() {
+        let limiter = RateLimiter::new();
+        limiter.update_from_headers(&headers(800, 0, unix_now() + 30));
+        let start = Instant::now();
+
+        limiter.acquire().await;
+
+        assert_about(start.elapsed(), Duration::from_secs(30));
+        assert!(!limiter.budget().unwrap().is_low());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn low_budget_spreads_requests_over_the_window() {
+        let limiter = RateLimiter::new();
+        limiter.update_from_headers(&headers(100, 5, unix_now() + 10));
+        assert!(limiter.budget().unwrap().is_low());
+        let start = Instant::now();
+
+        limiter.acquire().await;
+
+        // Four points left for the rest of the window: a fifth of it each
+        assert_about(start.elapsed() * 5, Duration::from_secs(10));
+        assert_eq!(limiter.budget().unwrap().remaining, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn budget_refills_once_the_reset_time_passed() {
+        let limiter = RateLimiter::new();
+        limiter.update_from_headers(&headers(800, 3, unix_now() + 2));
+
+        tokio::time::advance(Duration::from_secs(3)).await;
+
+        assert_eq!(limiter.budget().unwrap().remaining, 800);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn record_throttled_empties_the_bucket_until_reset() {
+        let limiter = RateLimiter::new();
+
+        let delay = limiter.record_throttled(&headers(800, 0, unix_now() + 20));
+
+        assert_about(delay, Duration::from_secs(20));
+        assert_eq!(limiter.budget().unwrap().remaining, 0);
+
+        // Twitch said there were points left, but the 429 says otherwise
+        let limiter = RateLimiter::new();
+        limiter.record_throttled(&headers(800, 12, unix_now() + 20));
+        assert_eq!(limiter.budget().unwrap().remaining, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn record_throttled_falls_back_without_a_usable_reset() {
+        let limiter = RateLimiter::new();
+        assert_eq!(
+            limiter.record_throttled(&HeaderMap::new()),
+            FALLBACK_RETRY_DELAY
+        );
+
+        let past_reset = headers(800, 0, unix_now() - 5);
+        assert_eq!(limiter.record_throttled(&past_reset), FALLBACK_RETRY_DELAY);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_delay_waits_for_the_reset_header() {
+        let mut reset = HeaderMap::new();
+        reset.insert("Ratelimit-Reset", (unix_now() + 15).into());
+        assert_about(retry_delay(&reset), Duration::from_secs(15));
+
+        reset.insert("Ratelimit-Reset", (unix_now() - 15).into());
+        assert_eq!(retry_delay(&reset), FALLBACK_RETRY_DELAY);
+
+        reset.insert("Ratelimit-Reset", "soon".parse().unwrap());
+        assert_eq!(retry_delay(&reset), FALLBACK_RETRY_DELAY);
+        assert_eq!(retry_delay(&HeaderMap::new()), FALLBACK_RETRY_DELAY);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn instant_from_unix_clamps_past_times_to_now() {
+        assert_eq!(instant_from_unix(unix_now() - 60), Instant::now());
+        assert_eq!(instant_from_unix(0), Instant::now());
+        assert_about(
+            instant_from_unix(unix_now() + 60) - Instant::now(),
+            Duration::from_secs(60),
+        );
+    }
+}
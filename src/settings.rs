@@ -0,0 +1,1061 @@
+// src/settings.rs
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::discord_sink;
+use crate::eventsub;
+use crate::eventsub_webhook;
+use crate::filters::{GameFilter, TitleFilter};
+use crate::push_sink;
+use crate::quiet_hours::{QuietAction, QuietSchedule};
+use crate::stream_state::EventKind;
+use crate::templates::{self, Escape};
+use crate::twitch_api::{self, Stream};
+use crate::webhook_sink;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    pub twitch_client_id: String,
+    pub twitch_client_secret: String,
+    // Plain logins or inline tables; merged with `[[streamer]]` by `load_settings`
+    #[serde(default)]
+    pub streamers: Vec<StreamerEntry>,
+    // `[[streamer]]` tables
+    #[serde(default, rename = "streamer")]
+    pub streamer_tables: Vec<StreamerConfig>,
+    // Events that produce notifications unless a streamer overrides it
+    #[serde(default = "default_notify")]
+    pub notify: Vec<EventKind>,
+    #[serde(default)]
+    pub notification_urgency: NotificationUrgency,
+    #[serde(default = "default_notification_timeout")]
+    pub notification_timeout_seconds: u32,
+    // Game and title changes replace the desktop notification of the stream
+    #[serde(default = "default_true")]
+    pub replace_notifications: bool,
+    #[serde(default)]
+    pub offline_notification: OfflineNotification,
+    // One or more `quiet_hours` rules
+    #[serde(default)]
+    pub quiet_hours: QuietSchedule,
+    #[serde(default)]
+    pub notification_actions: NotificationActionSettings,
+    // Summary and body of each kind of notification
+    #[serde(default)]
+    pub templates: NotificationTemplates,
+    // Coalescing of many notifications from the same check
+    #[serde(default)]
+    pub grouping: GroupingSettings,
+    #[serde(default)]
+    pub images: ImageSettings,
+    // `[[sink]]` tables; a single desktop sink if there are none
+    #[serde(default = "default_sinks", rename = "sink")]
+    pub sinks: Vec<SinkConfig>,
+    // A stream that comes back within this many seconds continues its session
+    #[serde(default = "default_restart_grace")]
+    pub restart_grace_seconds: u64,
+    #[serde(default)]
+    pub title_filter: TitleFilter,
+    #[serde(default)]
+    pub game_filter: GameFilter,
+    #[serde(default = "default_check_interval")]
+    pub check_interval_seconds: u64,
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    #[serde(default)]
+    pub eventsub: EventSubSettings,
+    #[serde(default)]
+    pub eventsub_webhook: EventSubWebhookSettings,
+    // How long shutdown waits for notifications still being delivered
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout_seconds: u64,
+    // Show the tray icon; false runs headless, like `--headless`
+    #[serde(default = "default_true")]
+    pub tray: bool,
+}
+
+/// An entry of the `streamers` list: a plain login or a full streamer table.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum StreamerEntry {
+    Login(String),
+    Config(Box<StreamerConfig>),
+}
+
+/// Per-streamer overrides. Anything left out falls back to the global setting.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StreamerConfig {
+    pub login: String,
+    // Name shown in notifications instead of the Twitch display name
+    pub alias: Option<String>,
+    // Icon name or path for this streamer's notifications
+    pub icon: Option<String>,
+    pub urgency: Option<NotificationUrgency>,
+    pub timeout_seconds: Option<u32>,
+    pub notify: Option<Vec<EventKind>>,
+    pub title_filter: Option<TitleFilter>,
+    pub game_filter: Option<GameFilter>,
+    // Replaces the global rules; `[]` turns quiet hours off for the streamer
+    pub quiet_hours: Option<QuietSchedule>,
+    // High-priority streamers get through quiet hours
+    #[serde(default)]
+    pub bypass_quiet_hours: bool,
+    // Keep tracking the streamer but never notify
+    #[serde(default)]
+    pub muted: bool,
+}
+
+impl StreamerConfig {
+    fn from_login(login: String) -> Self {
+        Self {
+            login,
+            alias: None,
+            icon: None,
+            urgency: None,
+            timeout_seconds: None,
+            notify: None,
+            title_filter: None,
+            game_filter: None,
+            quiet_hours: None,
+            bypass_quiet_hours: false,
+            muted: false,
+        }
+    }
+}
+
+/// Notification urgency as written in the config file.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationUrgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+/// What happens to a stream's desktop notification when the stream ends.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OfflineNotification {
+    /// The offline summary replaces it, if offline events are notified.
+    #[default]
+    Replace,
+    /// It is closed; an offline summary shows up on its own.
+    Close,
+}
+
+/// The effective settings for one streamer: their overrides applied on top of the
+/// global settings.
+#[derive(Debug)]
+pub struct StreamerProfile<'a> {
+    pub alias: Option<&'a str>,
+    pub icon: Option<&'a str>,
+    pub urgency: NotificationUrgency,
+    pub timeout_seconds: u32,
+    // Restarts are minor: low urgency and short-lived unless the streamer overrides it
+    pub back_online_urgency: NotificationUrgency,
+    pub back_online_timeout_seconds: u32,
+    pub notify: &'a [EventKind],
+    pub title_filter: &'a TitleFilter,
+    pub game_filter: &'a GameFilter,
+    pub quiet_hours: &'a QuietSchedule,
+    pub bypass_quiet_hours: bool,
+    pub muted: bool,
+}
+
+impl StreamerProfile<'_> {
+    /// The name to show for the streamer: their alias, or the Twitch display name.
+    pub fn display_name<'s>(&'s self, stream: &'s Stream) -> &'s str {
+        self.alias.unwrap_or(&stream.user_name)
+    }
+
+    /// What quiet hours do to the streamer's notifications at `now`, if anything.
+    pub fn quiet_action(&self, now: DateTime<Utc>) -> Option<QuietAction> {
+        if self.bypass_quiet_hours {
+            return None;
+        }
+        self.quiet_hours.action_at(now)
+    }
+
+    /// Urgency of the streamer's notifications for events of this kind.
+    pub fn urgency_for(&self, kind: EventKind) -> NotificationUrgency {
+        match kind {
+            EventKind::BackOnline => self.back_online_urgency,
+            _ => self.urgency,
+        }
+    }
+
+    /// How long the streamer's notifications for events of this kind stay on screen.
+    pub fn timeout_for(&self, kind: EventKind) -> u32 {
+        match kind {
+            EventKind::BackOnline => self.back_online_timeout_seconds,
+            _ => self.timeout_seconds,
+        }
+    }
+
+    /// True if events of this kind are notified for the streamer.
+    pub fn notifies(&self, kind: EventKind) -> bool {
+        self.notify.contains(&kind)
+    }
+}
+
+/// A notification backend and the events it receives.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct SinkConfig {
+    // Defaults to every event that passes the streamer's `notify` setting
+    #[serde(default)]
+    pub events: Option<Vec<EventKind>>,
+    #[serde(flatten)]
+    pub kind: SinkKind,
+}
+
+/// The backend of a sink, selected by its `type` key.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkKind {
+    /// Desktop notifications; uses the `images` and `notification_actions` settings.
+    Desktop,
+    /// Writes notifications to the log.
+    Log,
+    /// Sends a request to an HTTP endpoint.
+    Webhook(Box<WebhookSinkConfig>),
+    /// Posts embeds to a Discord channel webhook.
+    Discord(Box<DiscordSinkConfig>),
+    /// Publishes to an ntfy topic.
+    Ntfy(Box<NtfySinkConfig>),
+    /// Publishes to a Gotify server.
+    Gotify(Box<GotifySinkConfig>),
+}
+
+/// An ntfy topic. `title` and `message` are templates; they default to the
+/// notification's summary and body.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct NtfySinkConfig {
+    #[serde(default = "default_ntfy_server")]
+    pub server: String,
+    pub topic: String,
+    // Access token for protected topics
+    pub token: Option<String>,
+    pub title: Option<String>,
+    pub message: Option<String>,
+    // 1-5 per event kind; defaults follow the streamer's urgency
+    #[serde(default)]
+    pub priorities: HashMap<EventKind, u8>,
+    // Added to the tag ntfy shows for each event kind
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Attach the stream thumbnail
+    #[serde(default)]
+    pub attach_thumbnail: bool,
+}
+
+/// A Gotify application. `title` and `message` are templates; they default to the
+/// notification's summary and body.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct GotifySinkConfig {
+    pub server: String,
+    // Application token
+    pub token: String,
+    pub title: Option<String>,
+    pub message: Option<String>,
+    // 0-10 per event kind; defaults follow the streamer's urgency
+    #[serde(default)]
+    pub priorities: HashMap<EventKind, u8>,
+}
+
+/// A Discord channel webhook.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct DiscordSinkConfig {
+    pub webhook_url: String,
+    // Override the webhook's name and avatar
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+    // Role ID mentioned when any streamer goes live
+    pub mention_role: Option<String>,
+    // Role IDs keyed by login; these replace `mention_role` for that streamer
+    #[serde(default)]
+    pub role_mentions: HashMap<String, String>,
+}
+
+/// An outgoing webhook. The body is a template (see `templates::PLACEHOLDERS`).
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct WebhookSinkConfig {
+    pub url: String,
+    #[serde(default = "default_webhook_method")]
+    pub method: String,
+    // Sent with every request; Content-Type defaults to application/json
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default = "default_webhook_body")]
+    pub body: String,
+    // Signs the body with HMAC-SHA256, sent as `sha256=<hex>` in `signature_header`
+    pub hmac_secret: Option<String>,
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+    #[serde(default = "default_webhook_timeout")]
+    pub timeout_seconds: u64,
+    // Retries back off exponentially up to this delay
+    #[serde(default = "default_max_retry_delay")]
+    pub max_retry_delay_seconds: u64,
+    // Undelivered events are kept up to this many, and this long
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    #[serde(default = "default_max_event_age")]
+    pub max_event_age_seconds: u64,
+    // Defaults to a file in the cache directory
+    pub queue_file: Option<PathBuf>,
+}
+
+/// Actions offered on notifications. Commands are templates: they are split on
+/// whitespace, then `{login}`, `{name}`, `{user_id}`, `{title}`, `{game}` and `{url}`
+/// are filled in from the stream in each argument.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationActionSettings {
+    // Run when the notification itself is clicked; empty disables it
+    #[serde(default = "default_action_command")]
+    pub default: String,
+    #[serde(default = "default_actions", rename = "action")]
+    pub actions: Vec<NotificationActionConfig>,
+    // Offer a "Mute for today" button that silences the streamer until midnight
+    #[serde(default = "default_true")]
+    pub mute_for_today: bool,
+}
+
+impl Default for NotificationActionSettings {
+    fn default() -> Self {
+        Self {
+            default: default_action_command(),
+            actions: default_actions(),
+            mute_for_today: true,
+        }
+    }
+}
+
+/// A named button on a notification.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationActionConfig {
+    pub label: String,
+    pub command: String,
+}
+
+/// Notification text for each kind of event, as templates (see `templates::PLACEHOLDERS`).
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationTemplates {
+    #[serde(default = "default_live_template")]
+    pub live: TextTemplate,
+    #[serde(default = "default_game_template")]
+    pub game: TextTemplate,
+    // A game change into an allowed game after the go-live was filtered out
+    #[serde(default = "default_switched_into_game_template")]
+    pub switched_into_game: TextTemplate,
+    #[serde(default = "default_title_template")]
+    pub title: TextTemplate,
+    #[serde(default = "default_back_online_template")]
+    pub back_online: TextTemplate,
+    #[serde(default = "default_offline_template")]
+    pub offline: TextTemplate,
+}
+
+impl Default for NotificationTemplates {
+    fn default() -> Self {
+        Self {
+            live: default_live_template(),
+            game: default_game_template(),
+            switched_into_game: default_switched_into_game_template(),
+            title: default_title_template(),
+            back_online: default_back_online_template(),
+            offline: default_offline_template(),
+        }
+    }
+}
+
+impl NotificationTemplates {
+    /// Every template with its name in the config file.
+    fn all(&self) -> [(&'static str, &TextTemplate); 6] {
+        [
+            ("live", &self.live),
+            ("game", &self.game),
+            ("switched_into_game", &self.switched_into_game),
+            ("title", &self.title),
+            ("back_online", &self.back_online),
+            ("offline", &self.offline),
+        ]
+    }
+}
+
+/// The summary (first line) and body of a notification.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TextTemplate {
+    pub summary: String,
+    #[serde(default)]
+    pub body: String,
+}
+
+/// When many streamers change at once (e.g. everyone going live at 8pm), the
+/// notifications from one check are shown as a single summary instead.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GroupingSettings {
+    #[serde(default)]
+    pub mode: GroupingMode,
+    // Grouping kicks in with more than this many notifications from one check
+    #[serde(default = "default_group_threshold")]
+    pub threshold: usize,
+    // Events that can be grouped; others are always sent on their own
+    #[serde(default = "default_group_events")]
+    pub events: Vec<EventKind>,
+    // Streamers named in the summary before it says "and N more"
+    #[serde(default = "default_group_max_listed")]
+    pub max_listed: usize,
+}
+
+impl Default for GroupingSettings {
+    fn default() -> Self {
+        Self {
+            mode: GroupingMode::default(),
+            threshold: default_group_threshold(),
+            events: default_group_events(),
+            max_listed: default_group_max_listed(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupingMode {
+    /// A summary with a button that shows the notifications one by one.
+    #[default]
+    Summary,
+    /// Just the summary.
+    SummaryOnly,
+    /// Every notification on its own.
+    Off,
+}
+
+/// Images attached to notifications, cached under the XDG cache directory.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ImageSettings {
+    // Streamer profile pictures as the notification icon
+    #[serde(default = "default_true")]
+    pub avatars: bool,
+    // Stream preview images as the notification image
+    #[serde(default)]
+    pub thumbnails: bool,
+    #[serde(default = "default_thumbnail_width")]
+    pub thumbnail_width: u32,
+    #[serde(default = "default_thumbnail_height")]
+    pub thumbnail_height: u32,
+    #[serde(default = "default_avatar_cache")]
+    pub avatar_cache_seconds: u64,
+    #[serde(default = "default_thumbnail_cache")]
+    pub thumbnail_cache_seconds: u64,
+    // Overrides the cache directory
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        Self {
+            avatars: true,
+            thumbnails: false,
+            thumbnail_width: default_thumbnail_width(),
+            thumbnail_height: default_thumbnail_height(),
+            avatar_cache_seconds: default_avatar_cache(),
+            thumbnail_cache_seconds: default_thumbnail_cache(),
+            cache_dir: None,
+        }
+    }
+}
+
+/// Settings for the EventSub WebSocket transport. Polling keeps running alongside it.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct EventSubSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_eventsub_websocket_url")]
+    pub websocket_url: String,
+    // Override for the subscriptions endpoint, e.g. a local Twitch CLI mock server
+    pub subscriptions_url: Option<String>,
+    // WebSocket subscriptions require a User Access Token for this app's Client ID
+    pub user_access_token: Option<String>,
+}
+
+impl Default for EventSubSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            websocket_url: default_eventsub_websocket_url(),
+            subscriptions_url: None,
+            user_access_token: None,
+        }
+    }
+}
+
+/// Settings for the EventSub webhook receiver, for machines reachable over public HTTPS.
+/// The embedded server speaks plain HTTP; TLS is expected to end at a reverse proxy.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct EventSubWebhookSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_webhook_listen_address")]
+    pub listen_address: String,
+    #[serde(default = "default_webhook_path")]
+    pub path: String,
+    // Public HTTPS URL Twitch delivers to, forwarded to `listen_address` + `path`
+    #[serde(default)]
+    pub callback_url: String,
+    // 10-100 characters, used to sign every delivery
+    #[serde(default)]
+    pub secret: String,
+}
+
+impl Default for EventSubWebhookSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: default_webhook_listen_address(),
+            path: default_webhook_path(),
+            callback_url: String::new(),
+            secret: String::new(),
+        }
+    }
+}
+
+fn default_check_interval() -> u64 {
+    60 // Default to 60 seconds
+}
+
+fn default_notify() -> Vec<EventKind> {
+    vec![EventKind::Live, EventKind::Game, EventKind::Title]
+}
+
+fn default_notification_timeout() -> u32 {
+    10 // Show for 10 seconds
+}
+
+/// Timeout of back-online notifications for streamers without their own `timeout_seconds`.
+const BACK_ONLINE_TIMEOUT_SECONDS: u32 = 5;
+
+fn default_sinks() -> Vec<SinkConfig> {
+    vec![SinkConfig {
+        events: None,
+        kind: SinkKind::Desktop,
+    }]
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+fn default_webhook_body() -> String {
+    r#"{"event": "{event}", "login": "{login}", "name": "{name}", "title": "{title}", "game": "{game}", "viewers": {viewers}, "started_at": "{started_at}", "url": "{url}", "thumbnail_url": "{thumbnail_url}", "summary": "{summary}"}"#
+        .to_string()
+}
+
+fn default_signature_header() -> String {
+    "X-Signature-256".to_string()
+}
+
+fn default_webhook_timeout() -> u64 {
+    10
+}
+
+fn default_max_retry_delay() -> u64 {
+    300 // 5 minutes
+}
+
+fn default_queue_capacity() -> usize {
+    100
+}
+
+fn default_max_event_age() -> u64 {
+    60 * 60 // An hour
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_group_threshold() -> usize {
+    3
+}
+
+fn default_group_events() -> Vec<EventKind> {
+    vec![EventKind::Live, EventKind::BackOnline]
+}
+
+fn default_group_max_listed() -> usize {
+    5
+}
+
+fn text_template(summary: &str, body: &str) -> TextTemplate {
+    TextTemplate {
+        summary: summary.to_string(),
+        body: body.to_string(),
+    }
+}
+
+fn default_live_template() -> TextTemplate {
+    text_template("{name} just went live!", "Playing: {game}\n{title}")
+}
+
+fn default_game_template() -> TextTemplate {
+    text_template("{name} changed game to {game}!", "{title}")
+}
+
+fn default_switched_into_game_template() -> TextTemplate {
+    text_template(
+        "{name} is now playing {game}!",
+        "Switched from: {previous_game}\n{title}",
+    )
+}
+
+fn default_title_template() -> TextTemplate {
+    text_template("{name} changed title", "Playing: {game}\n{title}")
+}
+
+fn default_back_online_template() -> TextTemplate {
+    text_template("{name} is back online", "Playing: {game}\n{title}")
+}
+
+fn default_offline_template() -> TextTemplate {
+    text_template(
+        "{name} went offline",
+        "Streamed for {duration} (peak {peak_viewers} viewers)\nPlayed: {games_played}",
+    )
+}
+
+fn default_action_command() -> String {
+    "xdg-open {url}".to_string()
+}
+
+fn default_actions() -> Vec<NotificationActionConfig> {
+    vec![
+        NotificationActionConfig {
+            label: "Open in browser".to_string(),
+            command: "xdg-open {url}".to_string(),
+        },
+        NotificationActionConfig {
+            label: "Open chat".to_string(),
+            command: "xdg-open https://www.twitch.tv/popout/{login}/chat".to_string(),
+        },
+    ]
+}
+
+fn default_thumbnail_width() -> u32 {
+    440
+}
+
+fn default_thumbnail_height() -> u32 {
+    248
+}
+
+fn default_avatar_cache() -> u64 {
+    7 * 24 * 60 * 60 // Profile pictures rarely change: a week
+}
+
+fn default_thumbnail_cache() -> u64 {
+    5 * 60 // Twitch refreshes thumbnails about every 5 minutes
+}
+
+fn default_webhook_listen_address() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+fn default_webhook_path() -> String {
+    "/eventsub".to_string()
+}
+
+fn default_restart_grace() -> u64 {
+    120 // Default to 2 minutes
+}
+
+fn default_shutdown_timeout() -> u64 {
+    10
+}
+
+fn default_max_concurrent_requests() -> usize {
+    twitch_api::DEFAULT_MAX_CONCURRENT_REQUESTS
+}
+
+fn default_eventsub_websocket_url() -> String {
+    eventsub::DEFAULT_WEBSOCKET_URL.to_string()
+}
+
+impl Settings {
+    /// Parses a config file the way `load_settings` does, without the validation.
+    #[cfg(test)]
+    pub fn from_toml(toml: &str) -> Result<Self, config::ConfigError> {
+        let mut settings: Settings = config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+        settings.normalize_streamers()?;
+        Ok(settings)
+    }
+
+    /// Moves plain and inline `streamers` entries into `streamer_tables`, so that
+    /// every monitored streamer has exactly one table. Duplicate logins are an error.
+    fn normalize_streamers(&mut self) -> Result<(), config::ConfigError> {
+        let entries = std::mem::take(&mut self.streamers);
+        let mut configs: Vec<StreamerConfig> = entries
+            .into_iter()
+            .map(|entry| match entry {
+                StreamerEntry::Login(login) => StreamerConfig::from_login(login),
+                StreamerEntry::Config(config) => *config,
+            })
+            .collect();
+        configs.append(&mut self.streamer_tables);
+
+        let mut seen = HashSet::new();
+        for config in &configs {
+            if !seen.insert(config.login.to_lowercase()) {
+                return Err(config::ConfigError::Message(format!(
+                    "streamer '{}' is configured more than once",
+                    config.login
+                )));
+            }
+        }
+
+        self.streamer_tables = configs;
+        Ok(())
+    }
+
+    /// The settings that differ in `reloaded` but are only read at startup, as named
+    /// in the config file.
+    pub fn changes_needing_restart(&self, reloaded: &Settings) -> Vec<&'static str> {
+        let changes = [
+            // The users and EventSub subscriptions were set up for the old list
+            ("the streamer list", self.logins() != reloaded.logins()),
+            (
+                "the Twitch credentials",
+                self.twitch_client_id != reloaded.twitch_client_id
+                    || self.twitch_client_secret != reloaded.twitch_client_secret,
+            ),
+            (
+                "restart_grace_seconds",
+                self.restart_grace_seconds != reloaded.restart_grace_seconds,
+            ),
+            (
+                "max_concurrent_requests",
+                self.max_concurrent_requests != reloaded.max_concurrent_requests,
+            ),
+            ("[eventsub]", self.eventsub != reloaded.eventsub),
+            (
+                "[eventsub_webhook]",
+                self.eventsub_webhook != reloaded.eventsub_webhook,
+            ),
+            ("tray", self.tray != reloaded.tray),
+        ];
+        changes
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// The login names of every monitored streamer.
+    pub fn logins(&self) -> Vec<String> {
+        self.streamer_tables
+            .iter()
+            .map(|s| s.login.clone())
+            .collect()
+    }
+
+    fn streamer_config(&self, login: &str) -> Option<&StreamerConfig> {
+        self.streamer_tables
+            .iter()
+            .find(|s| s.login.eq_ignore_ascii_case(login))
+    }
+
+    /// Resolves the effective settings for a streamer.
+    pub fn profile(&self, login: &str) -> StreamerProfile<'_> {
+        let config = self.streamer_config(login);
+        StreamerProfile {
+            alias: config.and_then(|c| c.alias.as_deref()),
+            icon: config.and_then(|c| c.icon.as_deref()),
+            urgency: config
+                .and_then(|c| c.urgency)
+                .unwrap_or(self.notification_urgency),
+            timeout_seconds: config
+                .and_then(|c| c.timeout_seconds)
+                .unwrap_or(self.notification_timeout_seconds),
+            back_online_urgency: config
+                .and_then(|c| c.urgency)
+                .unwrap_or(NotificationUrgency::Low),
+            back_online_timeout_seconds: config
+                .and_then(|c| c.timeout_seconds)
+                .unwrap_or(BACK_ONLINE_TIMEOUT_SECONDS),
+            notify: config
+                .and_then(|c| c.notify.as_deref())
+                .unwrap_or(&self.notify),
+            title_filter: config
+                .and_then(|c| c.title_filter.as_ref())
+                .unwrap_or(&self.title_filter),
+            game_filter: config
+                .and_then(|c| c.game_filter.as_ref())
+                .unwrap_or(&self.game_filter),
+            quiet_hours: config
+                .and_then(|c| c.quiet_hours.as_ref())
+                .unwrap_or(&self.quiet_hours),
+            bypass_quiet_hours: config.is_some_and(|c| c.bypass_quiet_hours),
+            muted: config.is_some_and(|c| c.muted),
+        }
+    }
+}
+
+/// `$XDG_CACHE_HOME/twitch-notifier`, falling back to `~/.cache/twitch-notifier`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("twitch-notifier"))
+}
+
+pub fn load_settings() -> Result<Settings, config::ConfigError> {
+    let config_file_name = "config.toml";
+
+    info!(
+        "Attempting to load configuration from '{}'",
+        config_file_name
+    );
+
+    let settings = config::Config::builder()
+        // Look for `config.toml` in the current directory
+        .add_source(config::File::with_name(config_file_name).required(true))
+        // Add environment variable overrides (optional)
+        // e.g., `APP_TWITCH_CLIENT_ID=...` would override `twitch_client_id`
+        .add_source(
+            config::Environment::with_prefix("APP")
+                .separator("__")
+                .ignore_empty(true),
+        )
+        .build()?;
+
+    // Deserialize the configuration
+    let mut settings: Settings = settings.try_deserialize()?;
+    settings.normalize_streamers()?;
+
+    if let Some(action) = settings
+        .notification_actions
+        .actions
+        .iter()
+        .find(|a| a.command.trim().is_empty())
+    {
+        return Err(config::ConfigError::Message(format!(
+            "notification action '{}' has no command",
+            action.label
+        )));
+    }
+
+    for (kind, template) in settings.templates.all() {
+        for (part, text) in [("summary", &template.summary), ("body", &template.body)] {
+            templates::validate(text, Escape::None, false).map_err(|e| {
+                config::ConfigError::Message(format!("templates.{}.{}: {}", kind, part, e))
+            })?;
+        }
+    }
+
+    for sink in &settings.sinks {
+        let result = match &sink.kind {
+            SinkKind::Desktop | SinkKind::Log => Ok(()),
+            SinkKind::Webhook(config) => webhook_sink::validate(config),
+            SinkKind::Discord(config) => discord_sink::validate(config),
+            SinkKind::Ntfy(config) => push_sink::validate_ntfy(config),
+            SinkKind::Gotify(config) => push_sink::validate_gotify(config),
+        };
+        result.map_err(config::ConfigError::Message)?;
+    }
+
+    // Two workers on one queue file would deliver its events twice
+    let mut queue_files = HashSet::new();
+    for sink in &settings.sinks {
+        let SinkKind::Webhook(config) = &sink.kind else {
+            continue;
+        };
+        if let Some(file) = webhook_sink::queue_file(config) {
+            if !queue_files.insert(file.clone()) {
+                return Err(config::ConfigError::Message(format!(
+                    "webhook sinks for {} share the queue file {}; give each its own `queue_file`",
+                    config.url,
+                    file.display()
+                )));
+            }
+        }
+    }
+
+    let webhook = &settings.eventsub_webhook;
+    if webhook.enabled {
+        if webhook.callback_url.is_empty() {
+            return Err(config::ConfigError::Message(
+                "eventsub_webhook.callback_url is required when the webhook receiver is enabled"
+                    .to_string(),
+            ));
+        }
+        if !eventsub_webhook::SECRET_LENGTH.contains(&webhook.secret.len()) {
+            return Err(config::ConfigError::Message(
+                "eventsub_webhook.secret must be between 10 and 100 characters".to_string(),
+            ));
+        }
+    }
+
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREDENTIALS: &str = r#"
+        twitch_client_id = "id"
+        twitch_client_secret = "secret"
+    "#;
+
+    #[test]
+    fn streamers_accepts_logins_and_inline_tables() {
+        let settings = Settings::from_toml(&format!(
+            r#"{}
+            streamers = ["plain_user", {{ login = "inline_user", alias = "Inline" }}]
+
+            [[streamer]]
+            login = "table_user"
+            muted = true
+            "#,
+            CREDENTIALS
+        ))
+        .unwrap();
+
+        assert_eq!(
+            settings.logins(),
+            vec!["plain_user", "inline_user", "table_user"]
+        );
+        assert_eq!(settings.profile("inline_user").alias, Some("Inline"));
+        assert!(settings.profile("table_user").muted);
+        assert!(settings.streamers.is_empty());
+    }
+
+    #[test]
+    fn streamer_tables_reject_unknown_keys() {
+        let result = Settings::from_toml(&format!(
+            r#"{}
+            streamers = [{{ login = "inline_user", alais = "Typo" }}]
+            "#,
+            CREDENTIALS
+        ));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn duplicate_streamers_are_rejected_across_lists_and_tables() {
+        let result = Settings::from_toml(&format!(
+            r#"{}
+            streamers = ["Cooler_User"]
+
+            [[streamer]]
+            login = "cooler_user"
+            "#,
+            CREDENTIALS
+        ));
+
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("configured more than once"), "{}", error);
+
+        let result = Settings::from_toml(&format!(
+            r#"{}
+            streamers = ["cooler_user", "COOLER_USER"]
+            "#,
+            CREDENTIALS
+        ));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn streamer_overrides_fall_back_to_global_settings() {
+        let settings = Settings::from_toml(&format!(
+            r#"{}
+            streamers = ["plain_user"]
+            notify = ["live", "offline"]
+            notification_urgency = "low"
+            notification_timeout_seconds = 20
+
+            [[streamer]]
+            login = "Special_User"
+            urgency = "critical"
+            notify = ["live"]
+            "#,
+            CREDENTIALS
+        ))
+        .unwrap();
+
+        let special = settings.profile("special_user");
+        assert_eq!(special.urgency, NotificationUrgency::Critical);
+        assert_eq!(special.notify, &[EventKind::Live]);
+        assert_eq!(special.timeout_seconds, 20);
+        assert!(std::ptr::eq(special.title_filter, &settings.title_filter));
+        assert!(std::ptr::eq(special.quiet_hours, &settings.quiet_hours));
+
+        let plain = settings.profile("plain_user");
+        assert_eq!(plain.urgency, NotificationUrgency::Low);
+        assert_eq!(plain.notify, &[EventKind::Live, EventKind::Offline]);
+        assert_eq!(plain.alias, None);
+        assert!(!plain.muted);
+        // Restarts stay low-key unless the streamer sets an urgency
+        assert_eq!(
+            plain.urgency_for(EventKind::BackOnline),
+            NotificationUrgency::Low
+        );
+        assert_eq!(
+            special.urgency_for(EventKind::BackOnline),
+            NotificationUrgency::Critical
+        );
+    }
+
+    #[test]
+    fn reload_reports_settings_only_read_at_startup() {
+        let base = format!("{}\nstreamers = [\"cooler_user\"]\n", CREDENTIALS);
+        let settings = Settings::from_toml(&base).unwrap();
+
+        let same = Settings::from_toml(&format!("{}check_interval_seconds = 30\n", base)).unwrap();
+        assert!(settings.changes_needing_restart(&same).is_empty());
+
+        let changed = Settings::from_toml(&format!(
+            r#"{}
+            tray = false
+
+            [eventsub]
+            enabled = true
+            user_access_token = "token"
+
+            [eventsub_webhook]
+            secret = "A_RANDOM_SECRET"
+            "#,
+            base.replace("\"secret\"", "\"new-secret\"")
+        ))
+        .unwrap();
+        assert_eq!(
+            settings.changes_needing_restart(&changed),
+            [
+                "the Twitch credentials",
+                "[eventsub]",
+                "[eventsub_webhook]",
+                "tray"
+            ]
+        );
+    }
+}
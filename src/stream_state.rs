@@ -0,0 +1,674 @@
+// src/stream_state.rs
+
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::twitch_api::Stream;
+
+/// How long a stream reported live by EventSub is kept even though `/streams`
+/// does not list it yet, so a poll doesn't mistake Helix lag for going offline.
+const EVENTSUB_HELIX_LAG: Duration = Duration::from_secs(5 * 60);
+
+/// A transition in a streamer's lifecycle, produced by `StreamTracker`.
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// Offline -> Live
+    WentLive { stream: Stream },
+    /// Live, with a different game than before.
+    GameChanged {
+        stream: Stream,
+        previous_game_id: String,
+        previous_game: String,
+    },
+    /// Live, with a different title than before.
+    TitleChanged {
+        stream: Stream,
+        previous_title: String,
+    },
+    /// Live again within the restart grace period: a continuation, not a new go-live.
+    BackOnline {
+        stream: Stream,
+        offline_for: Duration,
+    },
+    /// Live -> Offline, with a summary of the session that just ended.
+    WentOffline {
+        /// The stream as it was last seen.
+        stream: Stream,
+        duration: Duration,
+        peak_viewers: u64,
+        games_played: Vec<String>,
+    },
+}
+
+/// The kind of a `StreamEvent`, as named in the `notify` setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Live,
+    Game,
+    Title,
+    BackOnline,
+    Offline,
+}
+
+impl EventKind {
+    /// The name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Live => "live",
+            EventKind::Game => "game",
+            EventKind::Title => "title",
+            EventKind::BackOnline => "back_online",
+            EventKind::Offline => "offline",
+        }
+    }
+}
+
+impl StreamEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            StreamEvent::WentLive { .. } => EventKind::Live,
+            StreamEvent::GameChanged { .. } => EventKind::Game,
+            StreamEvent::TitleChanged { .. } => EventKind::Title,
+            StreamEvent::BackOnline { .. } => EventKind::BackOnline,
+            StreamEvent::WentOffline { .. } => EventKind::Offline,
+        }
+    }
+
+    /// The stream the event is about.
+    pub fn stream(&self) -> &Stream {
+        match self {
+            StreamEvent::WentLive { stream }
+            | StreamEvent::GameChanged { stream, .. }
+            | StreamEvent::TitleChanged { stream, .. }
+            | StreamEvent::BackOnline { stream, .. }
+            | StreamEvent::WentOffline { stream, .. } => stream,
+        }
+    }
+}
+
+/// What is tracked about a stream while it is live.
+#[derive(Debug, Clone)]
+struct LiveSession {
+    stream: Stream,
+    // `started_at` of the first stream of the session; restarts get a new one
+    session_started_at: String,
+    first_seen: Instant,
+    peak_viewers: u64,
+    games_played: Vec<String>,
+    // Set while only EventSub has reported the stream and `/streams` hasn't listed it yet
+    awaiting_poll_since: Option<Instant>,
+}
+
+impl LiveSession {
+    fn new(stream: Stream) -> Self {
+        let mut session = Self {
+            session_started_at: stream.started_at.clone(),
+            peak_viewers: stream.viewer_count,
+            games_played: Vec::new(),
+            stream,
+            first_seen: Instant::now(),
+            awaiting_poll_since: None,
+        };
+        session.record_game();
+        session
+    }
+
+    fn record_game(&mut self) {
+        let game = &self.stream.game_name;
+        if !game.is_empty() && !self.games_played.contains(game) {
+            self.games_played.push(game.clone());
+        }
+    }
+
+    /// Session length so far from its first `started_at`, or from when we first saw it.
+    fn duration(&self) -> Duration {
+        chrono::DateTime::parse_from_rfc3339(&self.session_started_at)
+            .ok()
+            .and_then(|started| {
+                chrono::Utc::now()
+                    .signed_duration_since(started)
+                    .to_std()
+                    .ok()
+            })
+            .unwrap_or_else(|| self.first_seen.elapsed())
+    }
+
+    fn into_offline_event(self, duration: Duration) -> StreamEvent {
+        StreamEvent::WentOffline {
+            duration,
+            peak_viewers: self.peak_viewers,
+            games_played: self.games_played,
+            stream: self.stream,
+        }
+    }
+}
+
+/// Per-streamer state: Offline -> Live -> Ending -> Offline.
+/// `Ending` holds a dropped stream for the restart grace period, so a stream that
+/// comes back within it continues the same session.
+#[derive(Debug, Clone, Default)]
+enum StreamerState {
+    #[default]
+    Offline,
+    Live(Box<LiveSession>),
+    Ending {
+        session: Box<LiveSession>,
+        dropped_at: Instant,
+        // Session length at the moment the stream dropped
+        duration: Duration,
+    },
+}
+
+/// Tracks the lifecycle of every monitored streamer and turns observations from
+/// polling or EventSub into typed `StreamEvent`s.
+#[derive(Debug)]
+pub struct StreamTracker {
+    states: HashMap<String, StreamerState>,
+    restart_grace: Duration,
+}
+
+impl StreamTracker {
+    /// Creates a tracker; streams that come back within `restart_grace` of dropping
+    /// continue their session instead of counting as a new go-live.
+    pub fn new(restart_grace: Duration) -> Self {
+        Self {
+            states: HashMap::new(),
+            restart_grace,
+        }
+    }
+
+    pub fn is_live(&self, user_id: &str) -> bool {
+        matches!(self.states.get(user_id), Some(StreamerState::Live(_)))
+    }
+
+    /// The streams that are live right now, with how long each session has run.
+    pub fn live_streams(&self) -> Vec<(&Stream, Duration)> {
+        self.states
+            .values()
+            .filter_map(|state| match state {
+                StreamerState::Live(session) => Some((&session.stream, session.duration())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Records that a stream is live and returns the transitions this causes.
+    pub fn observe_live(&mut self, stream: Stream) -> Vec<StreamEvent> {
+        let user_id = stream.user_id.clone();
+        let state = self.states.remove(&user_id).unwrap_or_default();
+        let mut events = Vec::new();
+
+        let session = match state {
+            StreamerState::Offline => {
+                events.push(StreamEvent::WentLive {
+                    stream: stream.clone(),
+                });
+                Box::new(LiveSession::new(stream))
+            }
+            StreamerState::Ending {
+                session,
+                dropped_at,
+                duration,
+            } => {
+                let offline_for = dropped_at.elapsed();
+                if offline_for < self.restart_grace {
+                    if stream.id != session.stream.id {
+                        debug!(
+                            user_id,
+                            "Stream restarted with a new ID, continuing the session"
+                        );
+                    }
+                    events.push(StreamEvent::BackOnline {
+                        stream: stream.clone(),
+                        offline_for,
+                    });
+                    Self::update_session(session, stream, &mut events)
+                } else {
+                    // Grace period ran out before it was expired
+                    events.push(session.into_offline_event(duration));
+                    events.push(StreamEvent::WentLive {
+                        stream: stream.clone(),
+                    });
+                    Box::new(LiveSession::new(stream))
+                }
+            }
+            StreamerState::Live(session) => Self::update_session(session, stream, &mut events),
+        };
+
+        self.states.insert(user_id, StreamerState::Live(session));
+        events
+    }
+
+    /// Updates a live session with the latest stream data, noting game and title changes.
+    fn update_session(
+        mut session: Box<LiveSession>,
+        stream: Stream,
+        events: &mut Vec<StreamEvent>,
+    ) -> Box<LiveSession> {
+        if stream.game_id != session.stream.game_id {
+            events.push(StreamEvent::GameChanged {
+                stream: stream.clone(),
+                previous_game_id: session.stream.game_id.clone(),
+                previous_game: session.stream.game_name.clone(),
+            });
+        }
+        if stream.title != session.stream.title {
+            events.push(StreamEvent::TitleChanged {
+                stream: stream.clone(),
+                previous_title: session.stream.title.clone(),
+            });
+        }
+        session.peak_viewers = session.peak_viewers.max(stream.viewer_count);
+        session.stream = stream;
+        session.record_game();
+        session
+    }
+
+    /// Like `observe_live`, for a stream reported by EventSub. Until a poll lists the
+    /// stream too, polls that miss it don't take it offline (see `EVENTSUB_HELIX_LAG`).
+    pub fn observe_live_from_eventsub(&mut self, stream: Stream) -> Vec<StreamEvent> {
+        let user_id = stream.user_id.clone();
+        let was_live = self.is_live(&user_id);
+        let events = self.observe_live(stream);
+        if !was_live {
+            if let Some(StreamerState::Live(session)) = self.states.get_mut(&user_id) {
+                session.awaiting_poll_since = Some(Instant::now());
+            }
+        }
+        events
+    }
+
+    /// Records that a streamer is offline. Without a restart grace period this returns
+    /// the offline event right away; otherwise the session waits in `Ending` until
+    /// `expire_grace_periods` gives up on it.
+    pub fn observe_offline(&mut self, user_id: &str) -> Option<StreamEvent> {
+        let Some(StreamerState::Live(session)) = self.states.remove(user_id) else {
+            // Not live (or already ending): nothing changes
+            return None;
+        };
+        let duration = session.duration();
+
+        if self.restart_grace.is_zero() {
+            self.states
+                .insert(user_id.to_string(), StreamerState::Offline);
+            return Some(session.into_offline_event(duration));
+        }
+
+        debug!(
+            user_id,
+            "Stream dropped, waiting for the restart grace period"
+        );
+        self.states.insert(
+            user_id.to_string(),
+            StreamerState::Ending {
+                session,
+                dropped_at: Instant::now(),
+                duration,
+            },
+        );
+        None
+    }
+
+    /// Ends every session whose stream has been gone for longer than the restart
+    /// grace period, returning their offline events.
+    pub fn expire_grace_periods(&mut self) -> Vec<StreamEvent> {
+        let expired: Vec<String> = self
+            .states
+            .iter()
+            .filter_map(|(user_id, state)| match state {
+                StreamerState::Ending { dropped_at, .. }
+                    if dropped_at.elapsed() >= self.restart_grace =>
+                {
+                    Some(user_id.clone())
+                }
+                _ => None,
+            })
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(
+                |user_id| match self.states.insert(user_id, StreamerState::Offline) {
+                    Some(StreamerState::Ending {
+                        session, duration, ..
+                    }) => Some(session.into_offline_event(duration)),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+
+    /// Applies a title/category update to a live stream. Updates for offline
+    /// channels are ignored; the next go-live carries the new values anyway.
+    pub fn apply_channel_update(
+        &mut self,
+        user_id: &str,
+        title: &str,
+        game_id: &str,
+        game_name: &str,
+    ) -> Vec<StreamEvent> {
+        let Some(StreamerState::Live(session)) = self.states.get(user_id) else {
+            return Vec::new();
+        };
+        let mut updated = session.stream.clone();
+        updated.title = title.to_string();
+        updated.game_id = game_id.to_string();
+        updated.game_name = game_name.to_string();
+        self.observe_live(updated)
+    }
+
+    /// Applies the result of polling `/streams`: listed streams are live, every other
+    /// tracked streamer is offline (unless EventSub reported them live moments ago).
+    /// Also expires restart grace periods that have run out.
+    pub fn apply_poll(&mut self, live_streams: Vec<Stream>) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        let mut seen = HashSet::with_capacity(live_streams.len());
+        for stream in live_streams {
+            let user_id = stream.user_id.clone();
+            events.extend(self.observe_live(stream));
+            if let Some(StreamerState::Live(session)) = self.states.get_mut(&user_id) {
+                session.awaiting_poll_since = None;
+            }
+            seen.insert(user_id);
+        }
+
+        let gone: Vec<String> = self
+            .states
+            .iter()
+            .filter_map(|(user_id, state)| match state {
+                StreamerState::Live(session) if !seen.contains(user_id) => {
+                    let awaiting_helix = session
+                        .awaiting_poll_since
+                        .is_some_and(|since| since.elapsed() < EVENTSUB_HELIX_LAG);
+                    if awaiting_helix {
+                        debug!(
+                            user_id,
+                            "Stream not listed by /streams yet, keeping it live"
+                        );
+                        None
+                    } else {
+                        Some(user_id.clone())
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+
+        events.extend(
+            gone.iter()
+                .filter_map(|user_id| self.observe_offline(user_id)),
+        );
+        events.extend(self.expire_grace_periods());
+        events
+    }
+}
+
+/// Formats a duration as e.g. "3h 12m" (or "12m" under an hour).
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER_ID: &str = "1337";
+
+    /// A live stream of `USER_ID`. `started_at` is left empty so session durations
+    /// come from the (paused) tokio clock instead of the wall clock.
+    fn stream(id: &str, game: &str, title: &str, viewers: u64) -> Stream {
+        Stream {
+            id: id.to_string(),
+            user_id: USER_ID.to_string(),
+            user_login: "cooler_user".to_string(),
+            user_name: "Cooler_User".to_string(),
+            game_id: format!("{}-id", game),
+            game_name: game.to_string(),
+            title: title.to_string(),
+            stream_type: "live".to_string(),
+            viewer_count: viewers,
+            started_at: String::new(),
+            thumbnail_url: String::new(),
+        }
+    }
+
+    fn kinds(events: &[StreamEvent]) -> Vec<EventKind> {
+        events.iter().map(StreamEvent::kind).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn first_sighting_goes_live_once() {
+        let mut tracker = StreamTracker::new(Duration::ZERO);
+
+        let events = tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 10)]);
+        assert_eq!(kinds(&events), vec![EventKind::Live]);
+        assert!(tracker.is_live(USER_ID));
+
+        let events = tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 12)]);
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn game_and_title_changes_carry_the_previous_values() {
+        let mut tracker = StreamTracker::new(Duration::ZERO);
+        tracker.observe_live(stream("1", "Just Chatting", "Hi", 10));
+
+        let events = tracker.observe_live(stream("1", "Minecraft", "Building", 10));
+        assert_eq!(kinds(&events), vec![EventKind::Game, EventKind::Title]);
+        match &events[0] {
+            StreamEvent::GameChanged {
+                stream,
+                previous_game_id,
+                previous_game,
+            } => {
+                assert_eq!(stream.game_name, "Minecraft");
+                assert_eq!(previous_game_id, "Just Chatting-id");
+                assert_eq!(previous_game, "Just Chatting");
+            }
+            other => panic!("expected a game change, got {:?}", other),
+        }
+        match &events[1] {
+            StreamEvent::TitleChanged { previous_title, .. } => assert_eq!(previous_title, "Hi"),
+            other => panic!("expected a title change, got {:?}", other),
+        }
+
+        let events =
+            tracker.apply_channel_update(USER_ID, "Still building", "Minecraft-id", "Minecraft");
+        assert_eq!(kinds(&events), vec![EventKind::Title]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn channel_updates_while_offline_are_ignored() {
+        let mut tracker = StreamTracker::new(Duration::ZERO);
+
+        let events = tracker.apply_channel_update(USER_ID, "Soon", "Minecraft-id", "Minecraft");
+
+        assert!(events.is_empty());
+        assert!(!tracker.is_live(USER_ID));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn going_offline_summarises_the_session() {
+        let mut tracker = StreamTracker::new(Duration::ZERO);
+        tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 10)]);
+        tokio::time::advance(Duration::from_secs(30 * 60)).await;
+        tracker.apply_poll(vec![stream("1", "Minecraft", "Hi", 250)]);
+        tokio::time::advance(Duration::from_secs(30 * 60)).await;
+        // Back to a game played before: listed once
+        tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 80)]);
+        tokio::time::advance(Duration::from_secs(15 * 60)).await;
+
+        let events = tracker.apply_poll(vec![]);
+
+        assert_eq!(kinds(&events), vec![EventKind::Offline]);
+        match &events[0] {
+            StreamEvent::WentOffline {
+                stream,
+                duration,
+                peak_viewers,
+                games_played,
+            } => {
+                assert_eq!(stream.viewer_count, 80);
+                assert_eq!(*duration, Duration::from_secs(75 * 60));
+                assert_eq!(*peak_viewers, 250);
+                assert_eq!(games_played, &["Just Chatting", "Minecraft"]);
+            }
+            other => panic!("expected an offline summary, got {:?}", other),
+        }
+        assert!(!tracker.is_live(USER_ID));
+        assert!(tracker.apply_poll(vec![]).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restart_within_the_grace_period_is_back_online() {
+        let mut tracker = StreamTracker::new(Duration::from_secs(120));
+        tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 10)]);
+        tokio::time::advance(Duration::from_secs(10 * 60)).await;
+
+        assert!(tracker.apply_poll(vec![]).is_empty());
+        assert!(!tracker.is_live(USER_ID));
+        tokio::time::advance(Duration::from_secs(60)).await;
+        let events = tracker.apply_poll(vec![stream("2", "Just Chatting", "Hi", 15)]);
+
+        assert_eq!(kinds(&events), vec![EventKind::BackOnline]);
+        match &events[0] {
+            StreamEvent::BackOnline {
+                stream,
+                offline_for,
+            } => {
+                assert_eq!(stream.id, "2");
+                assert_eq!(*offline_for, Duration::from_secs(60));
+            }
+            other => panic!("expected back online, got {:?}", other),
+        }
+        assert!(tracker.is_live(USER_ID));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restart_after_the_grace_period_is_a_new_session() {
+        let mut tracker = StreamTracker::new(Duration::from_secs(120));
+        tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 10)]);
+        tokio::time::advance(Duration::from_secs(10 * 60)).await;
+        assert!(tracker.observe_offline(USER_ID).is_none());
+        tokio::time::advance(Duration::from_secs(121)).await;
+
+        // No poll expired the grace period in between: the go-live does it
+        let events = tracker.observe_live(stream("2", "Minecraft", "Hi", 15));
+
+        assert_eq!(kinds(&events), vec![EventKind::Offline, EventKind::Live]);
+        match &events[0] {
+            StreamEvent::WentOffline {
+                stream, duration, ..
+            } => {
+                assert_eq!(stream.id, "1");
+                assert_eq!(*duration, Duration::from_secs(10 * 60));
+            }
+            other => panic!("expected an offline summary, got {:?}", other),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_grace_period_ends_the_session_on_the_next_poll() {
+        let mut tracker = StreamTracker::new(Duration::from_secs(120));
+        tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 10)]);
+        assert!(tracker.apply_poll(vec![]).is_empty());
+        tokio::time::advance(Duration::from_secs(119)).await;
+        assert!(tracker.apply_poll(vec![]).is_empty());
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        assert_eq!(kinds(&tracker.apply_poll(vec![])), vec![EventKind::Offline]);
+
+        let events = tracker.apply_poll(vec![stream("2", "Just Chatting", "Hi", 10)]);
+        assert_eq!(kinds(&events), vec![EventKind::Live]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_grace_period_goes_offline_immediately() {
+        let mut tracker = StreamTracker::new(Duration::ZERO);
+        tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 10)]);
+
+        let offline = tracker.observe_offline(USER_ID);
+        assert!(matches!(offline, Some(StreamEvent::WentOffline { .. })));
+
+        let events = tracker.observe_live(stream("2", "Just Chatting", "Hi", 10));
+        assert_eq!(kinds(&events), vec![EventKind::Live]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn eventsub_go_live_survives_polls_until_helix_catches_up() {
+        let mut tracker = StreamTracker::new(Duration::ZERO);
+        let events = tracker.observe_live_from_eventsub(stream("1", "Just Chatting", "Hi", 0));
+        assert_eq!(kinds(&events), vec![EventKind::Live]);
+
+        // `/streams` lags behind EventSub: polls that miss the stream keep it live
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(tracker.apply_poll(vec![]).is_empty());
+        assert!(tracker.is_live(USER_ID));
+
+        // Once listed, a later poll that misses it does take it offline
+        assert!(tracker
+            .apply_poll(vec![stream("1", "Just Chatting", "Hi", 5)])
+            .is_empty());
+        assert_eq!(kinds(&tracker.apply_poll(vec![])), vec![EventKind::Offline]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn eventsub_go_live_never_listed_ends_after_the_lag() {
+        let mut tracker = StreamTracker::new(Duration::ZERO);
+        tracker.observe_live_from_eventsub(stream("1", "Just Chatting", "Hi", 0));
+
+        tokio::time::advance(EVENTSUB_HELIX_LAG - Duration::from_secs(1)).await;
+        assert!(tracker.apply_poll(vec![]).is_empty());
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        assert_eq!(kinds(&tracker.apply_poll(vec![])), vec![EventKind::Offline]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn eventsub_offline_then_online_race_continues_the_session() {
+        let mut tracker = StreamTracker::new(Duration::from_secs(120));
+        tracker.apply_poll(vec![stream("1", "Just Chatting", "Hi", 10)]);
+
+        // EventSub reports the drop and the restart before any poll sees either
+        assert!(tracker.observe_offline(USER_ID).is_none());
+        tokio::time::advance(Duration::from_secs(5)).await;
+        let events = tracker.observe_live_from_eventsub(stream("2", "Just Chatting", "Hi", 0));
+        assert_eq!(kinds(&events), vec![EventKind::BackOnline]);
+
+        // A poll still listing nothing (Helix lag) must not end the session again
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(tracker.apply_poll(vec![]).is_empty());
+        assert!(tracker.is_live(USER_ID));
+    }
+
+    #[test]
+    fn session_duration_follows_started_at() {
+        let mut live = stream("1", "Just Chatting", "Hi", 10);
+        live.started_at = (chrono::Utc::now() - chrono::Duration::hours(2)).to_rfc3339();
+        let session = LiveSession::new(live);
+
+        let duration = session.duration();
+
+        assert!(duration >= Duration::from_secs(2 * 60 * 60));
+        assert!(duration < Duration::from_secs(2 * 60 * 60 + 60));
+    }
+
+    #[test]
+    fn format_duration_shows_hours_only_when_needed() {
+        assert_eq!(format_duration(Duration::from_secs(12 * 60 + 59)), "12m");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 60 * 60 + 12 * 60)),
+            "3h 12m"
+        );
+    }
+}
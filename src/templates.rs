@@ -0,0 +1,232 @@
+// src/templates.rs
+
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+use crate::notifications::StreamNotification;
+use crate::stream_state::format_duration;
+
+/// Placeholders available in every template, filled in from a `StreamNotification`.
+/// Values that don't apply to an event (e.g. `{previous_game}` when going live) are
+/// empty, except numbers, which are 0.
+pub const PLACEHOLDERS: &[&str] = &[
+    "event",
+    // The `Stream` fields, by their Helix names
+    "stream_id",
+    "user_id",
+    "user_login",
+    "user_name",
+    "game_id",
+    "game_name",
+    "type",
+    "title",
+    "viewer_count",
+    "started_at",
+    // Shorter names and derived values
+    "login",
+    "name",
+    "game",
+    "viewers",
+    "url",
+    "thumbnail_url",
+    "avatar_url",
+    "uptime",
+    "previous_game",
+    "previous_title",
+    "offline_for",
+    "duration",
+    "peak_viewers",
+    "games_played",
+];
+
+/// Placeholders for the rendered notification text. Only sink templates can use
+/// these; the notification text itself is what they are rendered from.
+pub const TEXT_PLACEHOLDERS: &[&str] = &["summary", "body"];
+
+/// Placeholders whose values are always numbers, so JSON templates may leave them unquoted.
+const NUMERIC_PLACEHOLDERS: &[&str] = &["viewer_count", "viewers", "peak_viewers"];
+
+/// Anything in braces that could be meant as a placeholder, so typos like `{Game}` or
+/// `{user-name}` are caught too. Whitespace and quotes are excluded so that JSON
+/// objects in webhook bodies, e.g. `{"color": 5}`, aren't taken for placeholders.
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\{([^{}\s"]+)\}"#).expect("placeholder pattern is valid"));
+
+/// How placeholder values are inserted into a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    None,
+    /// For templates of JSON documents: values are escaped as JSON string contents.
+    Json,
+}
+
+/// How long ago an RFC 3339 timestamp was, e.g. "1h 5m".
+fn elapsed_since(timestamp: &str) -> Option<String> {
+    let started = chrono::DateTime::parse_from_rfc3339(timestamp).ok()?;
+    let elapsed = chrono::Utc::now()
+        .signed_duration_since(started)
+        .to_std()
+        .ok()?;
+    Some(format_duration(elapsed))
+}
+
+fn value(name: &str, n: &StreamNotification) -> Option<String> {
+    let stream = &n.stream;
+    let session = n.session.as_ref();
+    let value = match name {
+        "event" => n.kind.as_str().to_string(),
+        "stream_id" => stream.id.clone(),
+        "user_id" => stream.user_id.clone(),
+        "user_login" | "login" => stream.user_login.clone(),
+        "user_name" => stream.user_name.clone(),
+        "name" => n.display_name.clone(),
+        "game_id" => stream.game_id.clone(),
+        "game_name" | "game" => stream.game_name.clone(),
+        "type" => stream.stream_type.clone(),
+        "title" => stream.title.clone(),
+        "viewer_count" | "viewers" => stream.viewer_count.to_string(),
+        "started_at" => stream.started_at.clone(),
+        "url" => n.url.clone(),
+        "thumbnail_url" => n.thumbnail_url.clone().unwrap_or_default(),
+        "avatar_url" => n.avatar_url.clone().unwrap_or_default(),
+        // For a stream that ended, how long it ran
+        "uptime" => match session {
+            Some(session) => format_duration(session.duration),
+            None => elapsed_since(&stream.started_at).unwrap_or_default(),
+        },
+        "previous_game" => n.previous_game.clone().unwrap_or_default(),
+        "previous_title" => n.previous_title.clone().unwrap_or_default(),
+        "offline_for" => n.offline_for.map(format_duration).unwrap_or_default(),
+        "duration" => session
+            .map(|s| format_duration(s.duration))
+            .unwrap_or_default(),
+        // Numeric, so JSON templates may leave it unquoted: 0 rather than empty
+        "peak_viewers" => session
+            .map(|s| s.peak_viewers.to_string())
+            .unwrap_or_else(|| "0".to_string()),
+        "games_played" => session
+            .map(|s| s.games_played.join(", "))
+            .unwrap_or_default(),
+        "summary" => n.summary.clone(),
+        "body" => n.body.clone(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn escape(value: String, escape: Escape) -> String {
+    match escape {
+        Escape::None => value,
+        Escape::Json => {
+            let quoted = serde_json::Value::String(value).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        }
+    }
+}
+
+fn render_with(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    PLACEHOLDER
+        .replace_all(template, |caps: &Captures| {
+            lookup(&caps[1]).unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Fills in the placeholders of a template. Unknown placeholders are left as they are;
+/// `validate` reports them when the config is loaded.
+pub fn render(template: &str, n: &StreamNotification, escaping: Escape) -> String {
+    render_with(template, |name| value(name, n).map(|v| escape(v, escaping)))
+}
+
+/// Checks that a template only uses known placeholders and, for JSON templates,
+/// that it renders to valid JSON. `{summary}` and `{body}` are only allowed with
+/// `allow_text`, i.e. in sink templates.
+pub fn validate(template: &str, escaping: Escape, allow_text: bool) -> Result<(), String> {
+    let known: Vec<&str> = if allow_text {
+        PLACEHOLDERS
+            .iter()
+            .chain(TEXT_PLACEHOLDERS)
+            .copied()
+            .collect()
+    } else {
+        PLACEHOLDERS.to_vec()
+    };
+    if let Some(unknown) = PLACEHOLDER
+        .captures_iter(template)
+        .map(|caps| caps[1].to_string())
+        .find(|name| !known.contains(&name.as_str()))
+    {
+        return Err(format!(
+            "unknown placeholder '{{{}}}', expected one of: {}",
+            unknown,
+            known.join(", ")
+        ));
+    }
+
+    if escaping == Escape::Json {
+        // Text samples are only valid inside a JSON string, so unquoted text placeholders fail
+        let sample = render_with(template, |name| {
+            let sample = if NUMERIC_PLACEHOLDERS.contains(&name) {
+                "1"
+            } else {
+                "text"
+            };
+            Some(sample.to_string())
+        });
+        serde_json::from_str::<serde_json::Value>(&sample)
+            .map_err(|e| format!("template is not valid JSON: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_placeholder_typos() {
+        for template in ["{Game}", "{user-name}", "{viewers2}", "Live: {name} {tite}"] {
+            assert!(
+                validate(template, Escape::None, false).is_err(),
+                "{} should be rejected",
+                template
+            );
+        }
+        assert!(validate("{name} is live: {game}", Escape::None, false).is_ok());
+        assert!(validate("{summary}", Escape::None, false).is_err());
+        assert!(validate("{summary}", Escape::None, true).is_ok());
+    }
+
+    #[test]
+    fn validate_json_requires_quoted_text_placeholders() {
+        assert!(validate(
+            r#"{"viewers": {viewers}, "title": "{title}"}"#,
+            Escape::Json,
+            true
+        )
+        .is_ok());
+        assert!(validate(r#"{"title": {title}}"#, Escape::Json, true).is_err());
+        assert!(validate(r#"{"name": {name}}"#, Escape::Json, true).is_err());
+    }
+
+    #[test]
+    fn json_template_renders_valid_json_for_live_events() {
+        let template = r#"{"name": "{name}", "title": "{title}", "viewers": {viewers}, "peak": {peak_viewers}, "uptime": "{duration}"}"#;
+        validate(template, Escape::Json, true).unwrap();
+
+        let mut notification = StreamNotification::sample();
+        notification.stream.title = r#"Say "hi" \ {name}"#.to_string();
+        let rendered = render(template, &notification, Escape::Json);
+        let json: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(json["title"], r#"Say "hi" \ {name}"#);
+        assert_eq!(json["viewers"], 42);
+        assert_eq!(json["peak"], 0);
+        assert_eq!(json["uptime"], "");
+    }
+
+    #[test]
+    fn validate_json_allows_literal_objects() {
+        let template = r#"{"embeds": [{"color": 5, "title": "{name}"}], "meta": {}}"#;
+        assert!(validate(template, Escape::Json, true).is_ok());
+    }
+}
@@ -0,0 +1,81 @@
+// src/tray.rs
+
+use chrono::{DateTime, Local};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::twitch_api::Stream;
+
+/// Updates from the monitor task to the tray icon.
+#[derive(Debug)]
+pub enum TrayUpdate {
+    /// Every streamer that is live right now.
+    LiveStreams(Vec<LiveStream>),
+    /// Notifications were paused, snoozed or resumed.
+    State(NotifyState),
+    /// Checking streams failed, e.g. because authentication failed or the API is
+    /// unreachable. Shown until `ErrorCleared`.
+    Error(String),
+    ErrorCleared,
+    /// `notification_actions.default` changed; menu clicks open streams with it.
+    OpenCommand(String),
+    /// The monitor task shut down; closes the tray.
+    Quit,
+}
+
+/// Whether notifications are going out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotifyState {
+    #[default]
+    Active,
+    Paused,
+    /// Paused until the given time, then active again.
+    Snoozed {
+        until: DateTime<Local>,
+    },
+}
+
+/// A live streamer as listed in the tray menu.
+#[derive(Debug, Clone)]
+pub struct LiveStream {
+    pub display_name: String,
+    pub stream: Stream,
+    pub uptime: Duration,
+}
+
+/// The monitor task's end of the channel to the tray icon. Without a tray (headless,
+/// or a build without the `tray` feature) updates go nowhere.
+#[derive(Debug, Clone)]
+pub struct TrayHandle {
+    tx: Option<mpsc::Sender<TrayUpdate>>,
+}
+
+impl TrayHandle {
+    pub fn channel(buffer: usize) -> (Self, mpsc::Receiver<TrayUpdate>) {
+        let (tx, rx) = mpsc::channel(buffer);
+        (Self { tx: Some(tx) }, rx)
+    }
+
+    pub fn headless() -> Self {
+        Self { tx: None }
+    }
+
+    /// Sends an update, waiting if the tray is behind.
+    pub async fn send(&self, update: TrayUpdate) {
+        if let Some(tx) = &self.tx {
+            if tx.send(update).await.is_err() {
+                debug!("Tray is gone, not updating it.");
+            }
+        }
+    }
+
+    /// Sends an update the next one supersedes, dropping it if the tray is behind.
+    pub fn send_latest(&self, update: TrayUpdate) {
+        if let Some(tx) = &self.tx {
+            if let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send(update) {
+                debug!("Tray is gone, not updating it.");
+            }
+        }
+    }
+}
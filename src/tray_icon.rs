@@ -0,0 +1,280 @@
+// src/tray_icon.rs
+
+use gtk::prelude::*;
+use libappindicator::{AppIndicator, AppIndicatorStatus};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::notifications;
+use crate::settings;
+use crate::stream_state::format_duration;
+use crate::tray::{LiveStream, NotifyState, TrayUpdate};
+use crate::AppMessage;
+
+const TITLE: &str = "Twitch Notifier";
+
+const IDLE_ICON: &str = "twitch-notifier-idle";
+const LIVE_ICON: &str = "twitch-notifier-live";
+const PAUSED_ICON: &str = "twitch-notifier-paused";
+const ERROR_ICON: &str = "twitch-notifier-error";
+
+/// The bundled icons, written to the cache directory at startup so the indicator
+/// can load them by name.
+const ICONS: [(&str, &[u8]); 4] = [
+    (
+        IDLE_ICON,
+        include_bytes!("../assets/icons/twitch-notifier-idle.svg"),
+    ),
+    (
+        LIVE_ICON,
+        include_bytes!("../assets/icons/twitch-notifier-live.svg"),
+    ),
+    (
+        PAUSED_ICON,
+        include_bytes!("../assets/icons/twitch-notifier-paused.svg"),
+    ),
+    (
+        ERROR_ICON,
+        include_bytes!("../assets/icons/twitch-notifier-error.svg"),
+    ),
+];
+
+// Longer error messages are cut off in the menu
+const MAX_ERROR_CHARS: usize = 80;
+
+// How long "Snooze 1h" holds notifications
+const SNOOZE: Duration = Duration::from_secs(60 * 60);
+
+// Used to open a stream when the notification default action is disabled
+const FALLBACK_OPEN_COMMAND: &str = "xdg-open {url}";
+
+fn open_command_or_fallback(command: &str) -> String {
+    match command.trim() {
+        "" => FALLBACK_OPEN_COMMAND,
+        command => command,
+    }
+    .to_string()
+}
+
+fn live_label(live: &LiveStream) -> String {
+    let uptime = format_duration(live.uptime);
+    if live.stream.game_name.is_empty() {
+        format!("{} ({})", live.display_name, uptime)
+    } else {
+        format!(
+            "{} — {} ({})",
+            live.display_name, live.stream.game_name, uptime
+        )
+    }
+}
+
+/// The system tray icon and its menu. Lives on the GTK thread; the monitor task
+/// reaches it through `TrayUpdate`s.
+pub struct Tray {
+    indicator: AppIndicator,
+    menu: gtk::Menu,
+    tx_app: mpsc::Sender<AppMessage>,
+    // Command that opens a stream clicked in the menu
+    open_command: String,
+    live: Vec<LiveStream>,
+    state: NotifyState,
+    // The last error, until checks succeed again
+    error: Option<String>,
+}
+
+impl Tray {
+    pub fn new(tx_app: mpsc::Sender<AppMessage>, open_command: &str) -> Self {
+        let mut indicator = match install_icons() {
+            Some(dir) => AppIndicator::with_path(TITLE, IDLE_ICON, &dir.to_string_lossy()),
+            None => AppIndicator::new(TITLE, IDLE_ICON),
+        };
+        indicator.set_status(AppIndicatorStatus::Active);
+        let mut tray = Self {
+            indicator,
+            menu: gtk::Menu::new(),
+            tx_app,
+            open_command: open_command_or_fallback(open_command),
+            live: Vec::new(),
+            state: NotifyState::Active,
+            error: None,
+        };
+        tray.rebuild();
+        tray
+    }
+
+    fn apply(&mut self, update: TrayUpdate) {
+        match update {
+            TrayUpdate::LiveStreams(live) => self.live = live,
+            TrayUpdate::State(state) => self.state = state,
+            TrayUpdate::Error(message) => self.error = Some(message),
+            TrayUpdate::ErrorCleared => self.error = None,
+            TrayUpdate::OpenCommand(command) => {
+                self.open_command = open_command_or_fallback(&command)
+            }
+            TrayUpdate::Quit => {
+                gtk::main_quit();
+                return;
+            }
+        }
+        self.rebuild();
+    }
+
+    fn state_label(&self) -> Option<String> {
+        match self.state {
+            NotifyState::Active => None,
+            NotifyState::Paused => Some("Paused".to_string()),
+            NotifyState::Snoozed { until } => {
+                Some(format!("Snoozed until {}", until.format("%H:%M")))
+            }
+        }
+    }
+
+    /// Shows the current state on the icon, with the live count and any pause in
+    /// its label. An error takes precedence over being paused, which takes
+    /// precedence over streams being live.
+    fn update_indicator(&mut self) {
+        let icon = if self.error.is_some() {
+            ERROR_ICON
+        } else if self.state != NotifyState::Active {
+            PAUSED_ICON
+        } else if !self.live.is_empty() {
+            LIVE_ICON
+        } else {
+            IDLE_ICON
+        };
+        let mut label: Vec<String> = Vec::new();
+        if !self.live.is_empty() {
+            label.push(format!("{} live", self.live.len()));
+        }
+        label.extend(self.state_label());
+        if self.error.is_some() {
+            label.push("Error".to_string());
+        }
+        let label = label.join(" · ");
+
+        self.indicator.set_icon_full(icon, &label);
+        self.indicator.set_label(&label, "");
+        if label.is_empty() {
+            self.indicator.set_title(TITLE);
+        } else {
+            self.indicator.set_title(&format!("{} ({})", TITLE, label));
+        }
+    }
+
+    /// Adds a menu item that sends `message` to the monitor task.
+    fn append_message_item(&self, label: &str, message: impl Fn() -> AppMessage + 'static) {
+        let item = gtk::MenuItem::with_label(label);
+        let tx_app = self.tx_app.clone();
+        item.connect_activate(move |_| {
+            if tx_app.blocking_send(message()).is_err() {
+                error!("Failed to send message to monitor task");
+            }
+        });
+        self.menu.append(&item);
+    }
+
+    /// Replaces the menu items with ones for the current state.
+    fn rebuild(&mut self) {
+        for child in self.menu.children() {
+            self.menu.remove(&child);
+        }
+
+        let title = gtk::MenuItem::with_label(TITLE);
+        title.set_sensitive(false);
+        self.menu.append(&title);
+        if let Some(message) = &self.error {
+            let mut text: String = message.chars().take(MAX_ERROR_CHARS).collect();
+            if text.len() < message.len() {
+                text.push('…');
+            }
+            let error = gtk::MenuItem::with_label(&format!("⚠ {}", text));
+            error.set_sensitive(false);
+            self.menu.append(&error);
+        }
+        self.menu.append(&gtk::SeparatorMenuItem::new());
+
+        if self.live.is_empty() {
+            let nobody = gtk::MenuItem::with_label("Nobody is live");
+            nobody.set_sensitive(false);
+            self.menu.append(&nobody);
+        }
+        for live in &self.live {
+            let item = gtk::MenuItem::with_label(&live_label(live));
+            let (command, stream) = (self.open_command.clone(), live.stream.clone());
+            item.connect_activate(move |_| {
+                info!("Opening {}'s stream from the tray menu.", stream.user_name);
+                notifications::spawn_command(&command, &stream);
+            });
+            self.menu.append(&item);
+        }
+        self.menu.append(&gtk::SeparatorMenuItem::new());
+
+        if let Some(label) = self.state_label() {
+            let state = gtk::MenuItem::with_label(&label);
+            state.set_sensitive(false);
+            self.menu.append(&state);
+        }
+        match self.state {
+            NotifyState::Active => {
+                self.append_message_item("Pause notifications", || AppMessage::Pause)
+            }
+            NotifyState::Paused | NotifyState::Snoozed { .. } => {
+                self.append_message_item("Resume notifications", || AppMessage::Resume)
+            }
+        }
+        self.append_message_item("Snooze 1h", || AppMessage::SnoozeFor(SNOOZE));
+        self.append_message_item("Check now", || AppMessage::CheckNow);
+        self.append_message_item("Reload configuration", || AppMessage::ReloadConfig);
+        self.menu.append(&gtk::SeparatorMenuItem::new());
+
+        let quit = gtk::MenuItem::with_label("Quit");
+        let quit_tx = self.tx_app.clone();
+        quit.connect_activate(move |_| {
+            info!("Quit menu item clicked.");
+            // The monitor task closes the tray once it has shut down
+            if quit_tx.blocking_send(AppMessage::Quit).is_err() {
+                debug!("Monitor task is gone, quitting right away.");
+                gtk::main_quit();
+            }
+        });
+        self.menu.append(&quit);
+
+        self.menu.show_all();
+        self.indicator.set_menu(&mut self.menu);
+        self.update_indicator();
+    }
+
+    /// Runs the GTK main loop until `TrayUpdate::Quit`. Updates from the monitor task are received
+    /// on the main context, so the menu is only ever touched from the GTK thread.
+    pub fn run(mut self, mut rx_tray: mpsc::Receiver<TrayUpdate>) {
+        gtk::glib::MainContext::default().spawn_local(async move {
+            while let Some(update) = rx_tray.recv().await {
+                self.apply(update);
+            }
+            debug!("Monitor task closed the tray channel.");
+        });
+        gtk::main();
+    }
+}
+
+/// Writes the bundled icons to `<cache dir>/icons` and returns that directory, or
+/// None if they can't be written and the indicator has to fall back to the theme.
+fn install_icons() -> Option<PathBuf> {
+    let dir = settings::cache_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join("twitch-notifier"))
+        .join("icons");
+    let written = std::fs::create_dir_all(&dir).and_then(|_| {
+        ICONS
+            .iter()
+            .try_for_each(|(name, svg)| std::fs::write(dir.join(format!("{}.svg", name)), svg))
+    });
+    match written {
+        Ok(()) => Some(dir),
+        Err(e) => {
+            warn!("Failed to write tray icons to {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
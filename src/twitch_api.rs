@@ -1,12 +1,20 @@
 #![allow(dead_code)] // TODO: Remove this when structs/errors are used
                      // src/twitch_api.rs
 
+use futures_util::stream::{self, StreamExt, TryStreamExt};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION}; // CONTENT_TYPE commented out
-use serde::Deserialize;
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
 #[allow(unused_imports)] // Allow trace for now
 use tracing::{debug, info, trace, warn};
 
+use crate::rate_limit::{self, RateLimitBudget, RateLimiter};
+
 /// Represents the response for getting an App Access Token.
 #[derive(Debug, Deserialize)]
 pub struct AppAccessTokenResponse {
@@ -15,20 +23,37 @@ pub struct AppAccessTokenResponse {
     pub token_type: String,
 }
 
+/// Represents the response of the `/oauth2/validate` endpoint.
+/// App Access Tokens carry no `login`/`user_id`, so only the shared fields are kept.
+#[derive(Debug, Deserialize)]
+pub struct ValidateTokenResponse {
+    pub client_id: String,
+    pub expires_in: u64,
+}
+
 /// Represents a Twitch User object from the API.
 #[derive(Debug, Deserialize, Clone)] // Clone needed to easily store user info
 pub struct User {
     pub id: String,
     pub login: String,
     pub display_name: String,
+    #[serde(default)]
+    pub profile_image_url: String,
 }
 
 /// Represents the generic wrapper for Twitch API data arrays.
 #[derive(Debug, Deserialize)]
 pub struct TwitchDataWrapper<T> {
     pub data: Vec<T>,
-    // We might need pagination later, but ignore for MVP
-    // pub pagination: Option<Pagination>,
+    #[serde(default)]
+    pub pagination: Option<Pagination>,
+}
+
+/// Represents the pagination object of a Twitch API response.
+/// The cursor is missing (or the object empty) on the last page.
+#[derive(Debug, Deserialize, Default)]
+pub struct Pagination {
+    pub cursor: Option<String>,
 }
 
 /// Represents a live Twitch Stream object from the API.
@@ -45,6 +70,49 @@ pub struct Stream {
     pub stream_type: String, // Should be "live" for online streams
     pub viewer_count: u64,
     pub started_at: String, // Consider parsing this to a DateTime object later
+    // Contains `{width}` and `{height}` placeholders for the requested size
+    #[serde(default)]
+    pub thumbnail_url: String,
+}
+
+/// Represents a Twitch Channel Information object from the API.
+/// Unlike `Stream`, this is available whether or not the channel is live.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChannelInformation {
+    pub broadcaster_id: String,
+    pub broadcaster_login: String,
+    pub broadcaster_name: String,
+    pub game_id: String,
+    pub game_name: String,
+    pub title: String,
+}
+
+/// Transport an EventSub subscription delivers its events through.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum EventSubTransport {
+    Websocket { session_id: String },
+    Webhook { callback: String, secret: String },
+}
+
+/// Represents an EventSub subscription as returned by the API.
+#[derive(Debug, Deserialize, Clone)]
+pub struct EventSubSubscription {
+    pub id: String,
+    pub status: String,
+    #[serde(rename = "type")]
+    pub subscription_type: String,
+    pub version: String,
+}
+
+/// Request body for creating an EventSub subscription.
+#[derive(Debug, Serialize)]
+struct CreateSubscriptionRequest<'a> {
+    #[serde(rename = "type")]
+    subscription_type: &'a str,
+    version: &'a str,
+    condition: serde_json::Value,
+    transport: &'a EventSubTransport,
 }
 
 #[derive(Debug, Error)]
@@ -64,6 +132,9 @@ pub enum ApiError {
     #[error("App Access Token missing or invalid")]
     MissingToken,
 
+    #[error("A User Access Token is required for EventSub WebSocket subscriptions")]
+    MissingUserToken,
+
     #[error("User not found: {0}")]
     UserNotFound(String),
 
@@ -72,7 +143,44 @@ pub enum ApiError {
 }
 
 const TWITCH_API_BASE_URL: &str = "https://api.twitch.tv/helix";
-const TWITCH_AUTH_URL: &str = "https://id.twitch.tv/oauth2/token";
+// `/token` and `/validate` live under this
+const TWITCH_AUTH_BASE_URL: &str = "https://id.twitch.tv/oauth2";
+
+/// Refresh the App Access Token this long before Twitch says it expires.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Helix rejects requests carrying more than this many `login`/`user_id` values.
+const MAX_IDS_PER_REQUEST: usize = 100;
+
+/// How many times a request rejected with 429 is retried after the bucket resets.
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Default number of batched requests allowed in flight at once.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Twitch requires apps to validate their tokens at least once an hour.
+pub const TOKEN_VALIDATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// An App Access Token together with the moment it stops being valid.
+#[derive(Debug, Clone)]
+struct AppToken {
+    value: String,
+    expires_at: Instant,
+}
+
+impl AppToken {
+    fn new(value: String, expires_in: u64) -> Self {
+        Self {
+            value,
+            expires_at: Instant::now() + Duration::from_secs(expires_in),
+        }
+    }
+
+    /// True once the token is within `TOKEN_REFRESH_MARGIN` of expiring.
+    fn needs_refresh(&self) -> bool {
+        Instant::now() + TOKEN_REFRESH_MARGIN >= self.expires_at
+    }
+}
 
 /// Client for interacting with the Twitch API.
 #[derive(Debug)]
@@ -80,7 +188,16 @@ pub struct TwitchClient {
     client: reqwest::Client,
     client_id: String,
     client_secret: String,
-    access_token: Option<String>, // Store the App Access Token
+    // The App Access Token, shared so that concurrent requests refresh it only once
+    token: Mutex<Option<AppToken>>,
+    max_concurrent_requests: usize,
+    rate_limiter: RateLimiter,
+    // WebSocket EventSub subscriptions must be created with a user token
+    user_access_token: Option<String>,
+    api_base_url: String,
+    auth_base_url: String,
+    // Defaults to the subscriptions endpoint under `api_base_url`
+    eventsub_subscriptions_url: Option<String>,
 }
 
 impl TwitchClient {
@@ -94,24 +211,79 @@ impl TwitchClient {
             client,
             client_id,
             client_secret,
-            access_token: None,
+            token: Mutex::new(None),
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            rate_limiter: RateLimiter::new(),
+            user_access_token: None,
+            api_base_url: TWITCH_API_BASE_URL.to_string(),
+            auth_base_url: TWITCH_AUTH_BASE_URL.to_string(),
+            eventsub_subscriptions_url: None,
         })
     }
 
-    /// Fetches or refreshes the App Access Token from Twitch.
-    pub async fn get_app_access_token(&mut self) -> Result<(), ApiError> {
-        // TODO: Check token expiry before fetching a new one
+    /// Sets how many batched requests may run concurrently (at least one).
+    pub fn with_max_concurrent_requests(mut self, limit: usize) -> Self {
+        self.max_concurrent_requests = limit.max(1);
+        self
+    }
+
+    /// Sets the User Access Token used to create WebSocket EventSub subscriptions.
+    /// It is generated for this app's Client ID; no scopes are needed for stream events.
+    pub fn with_user_access_token(mut self, token: Option<String>) -> Self {
+        self.user_access_token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    /// Overrides the Helix base URL, e.g. to point at a local mock server.
+    pub fn with_api_base_url(mut self, url: String) -> Self {
+        self.api_base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the OAuth base URL that tokens are fetched and validated under.
+    pub fn with_auth_base_url(mut self, url: String) -> Self {
+        self.auth_base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the EventSub subscriptions endpoint, e.g. to point at a local mock server.
+    pub fn with_eventsub_subscriptions_url(mut self, url: String) -> Self {
+        self.eventsub_subscriptions_url = Some(url);
+        self
+    }
+
+    fn eventsub_subscriptions_url(&self) -> String {
+        self.eventsub_subscriptions_url
+            .clone()
+            .unwrap_or_else(|| format!("{}/eventsub/subscriptions", self.api_base_url))
+    }
+
+    /// Returns the Helix rate-limit budget last reported by Twitch, if any.
+    /// Callers can use it to slow down their polling before requests start queueing.
+    pub fn rate_limit_budget(&self) -> Option<RateLimitBudget> {
+        self.rate_limiter.budget()
+    }
+
+    /// Fetches a new App Access Token from Twitch, replacing any stored one.
+    pub async fn get_app_access_token(&self) -> Result<(), ApiError> {
+        let mut token = self.token.lock().await;
+        *token = Some(self.fetch_app_access_token().await?);
+        Ok(())
+    }
+
+    /// Requests a new App Access Token using the client credentials flow.
+    async fn fetch_app_access_token(&self) -> Result<AppToken, ApiError> {
         info!("Fetching new App Access Token from Twitch");
 
         let params = [
-            ("client_id", &self.client_id),
-            ("client_secret", &self.client_secret),
-            ("grant_type", &"client_credentials".to_string()), // Use constant later
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("grant_type", "client_credentials"),
         ];
 
         let response = self
             .client
-            .post(TWITCH_AUTH_URL)
+            .post(format!("{}/token", self.auth_base_url))
             .form(&params)
             .send()
             .await?;
@@ -122,30 +294,81 @@ impl TwitchClient {
                 "Received new token (expires in {}s)",
                 token_response.expires_in
             );
-            self.access_token = Some(token_response.access_token);
-            Ok(())
+            Ok(AppToken::new(
+                token_response.access_token,
+                token_response.expires_in,
+            ))
         } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "<Failed to read error body>".to_string());
-            warn!(status = %status, error_body = %error_text, "Failed to get App Access Token");
-            Err(ApiError::TwitchError {
-                status,
-                message: error_text,
-            })
+            Err(Self::error_from_response(response, "Failed to get App Access Token").await)
         }
     }
 
-    /// Gets Twitch User information for a list of login names.
-    pub async fn get_users_by_login(&self, logins: &[String]) -> Result<Vec<User>, ApiError> {
-        if logins.is_empty() {
-            return Ok(vec![]); // Nothing to fetch
+    /// Returns a usable App Access Token, fetching a new one if none is stored
+    /// or the stored one is about to expire.
+    async fn access_token(&self) -> Result<String, ApiError> {
+        let mut token = self.token.lock().await;
+        if let Some(current) = token.as_ref().filter(|t| !t.needs_refresh()) {
+            return Ok(current.value.clone());
         }
+        if token.is_some() {
+            info!("App Access Token is about to expire, refreshing");
+        }
+
+        let fresh = self.fetch_app_access_token().await?;
+        let value = fresh.value.clone();
+        *token = Some(fresh);
+        Ok(value)
+    }
+
+    /// Forgets the stored token if it is still `stale`, so the next request fetches a new one.
+    /// A token already replaced by a concurrent request is left alone.
+    async fn invalidate_token(&self, stale: &str) {
+        let mut token = self.token.lock().await;
+        if token.as_ref().is_some_and(|t| t.value == stale) {
+            *token = None;
+        }
+    }
 
-        let token = self.access_token.as_deref().ok_or(ApiError::MissingToken)?;
+    /// Checks the stored token against Twitch's `/oauth2/validate` endpoint.
+    /// Twitch asks apps to do this on startup and hourly. An expired or revoked
+    /// token is replaced with a new one; a valid one has its expiry updated.
+    pub async fn validate_token(&self) -> Result<(), ApiError> {
+        let token = self.access_token().await?;
 
+        debug!("Validating App Access Token with Twitch");
+        let response = self
+            .client
+            .get(format!("{}/validate", self.auth_base_url))
+            .header(AUTHORIZATION, format!("OAuth {}", token))
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => {
+                let validation: ValidateTokenResponse = response.json().await?;
+                debug!(
+                    "App Access Token is valid (expires in {}s)",
+                    validation.expires_in
+                );
+                let mut stored = self.token.lock().await;
+                if let Some(current) = stored.as_mut().filter(|t| t.value == token) {
+                    *current = AppToken::new(token, validation.expires_in);
+                }
+                Ok(())
+            }
+            StatusCode::UNAUTHORIZED => {
+                warn!("App Access Token is no longer valid, fetching a new one");
+                self.invalidate_token(&token).await;
+                self.access_token().await.map(|_| ())
+            }
+            _ => Err(
+                Self::error_from_response(response, "Failed to validate App Access Token").await,
+            ),
+        }
+    }
+
+    /// Builds the headers every Helix request needs.
+    fn auth_headers(&self, token: &str) -> HeaderMap {
         let mut headers = HeaderMap::new();
         headers.insert(
             AUTHORIZATION,
@@ -157,44 +380,190 @@ impl TwitchClient {
             HeaderValue::from_str(&self.client_id)
                 .expect("Failed to create client ID header value"),
         );
+        headers
+    }
 
-        // Build the URL with query parameters: ?login=user1&login=user2...
-        let url = format!("{}/users", TWITCH_API_BASE_URL);
-        let query_params: Vec<(String, String)> = logins
-            .iter()
-            .map(|login| ("login".to_string(), login.clone()))
-            .collect();
+    /// Sends an authenticated GET request to a Helix endpoint.
+    async fn get_helix<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query_params: &[(String, String)],
+        context: &str,
+    ) -> Result<T, ApiError> {
+        let url = format!("{}/{}", self.api_base_url, path);
+        self.send_helix(Method::GET, &url, query_params, None, None, context)
+            .await
+    }
 
-        debug!(logins = ?logins, "Fetching user data from Twitch API");
+    /// Sends an authenticated request to a Helix endpoint.
+    /// Requests wait for the rate limiter before being sent. A 401 response means the
+    /// App Access Token was rejected, so it is refreshed and the request retried once;
+    /// a 429 is retried after the bucket resets, up to `MAX_RATE_LIMIT_RETRIES` times.
+    /// When `user_token` is given it is used instead, and a 401 is returned as is.
+    async fn send_helix<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        query_params: &[(String, String)],
+        body: Option<&serde_json::Value>,
+        user_token: Option<&str>,
+        context: &str,
+    ) -> Result<T, ApiError> {
+        let mut retried_unauthorized = user_token.is_some();
+        let mut throttled_retries = 0;
+        // User Access Token requests count against that user's bucket, not the app's
+        let app_bucket = user_token.is_none();
+
+        loop {
+            let token = match user_token {
+                Some(token) => token.to_string(),
+                None => self.access_token().await?,
+            };
+            if app_bucket {
+                self.rate_limiter.acquire().await;
+            }
+            let mut request = self
+                .client
+                .request(method.clone(), url)
+                .headers(self.auth_headers(&token))
+                .query(query_params);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            let response = request.send().await?;
+
+            match response.status() {
+                StatusCode::UNAUTHORIZED if !retried_unauthorized => {
+                    warn!("Twitch rejected the App Access Token, refreshing and retrying");
+                    self.invalidate_token(&token).await;
+                    retried_unauthorized = true;
+                }
+                StatusCode::TOO_MANY_REQUESTS if throttled_retries < MAX_RATE_LIMIT_RETRIES => {
+                    let delay = if app_bucket {
+                        self.rate_limiter.record_throttled(response.headers())
+                    } else {
+                        rate_limit::retry_delay(response.headers())
+                    };
+                    throttled_retries += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                status => {
+                    if app_bucket {
+                        self.rate_limiter.update_from_headers(response.headers());
+                    }
+                    if status.is_success() {
+                        return Ok(response.json().await?);
+                    }
+                    return Err(Self::error_from_response(response, context).await);
+                }
+            }
+        }
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .headers(headers)
-            .query(&query_params)
-            .send()
-            .await?;
+    /// Fetches every page of a Helix endpoint by following `pagination.cursor`.
+    async fn get_helix_paginated<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query_params: &[(String, String)],
+        context: &str,
+    ) -> Result<Vec<T>, ApiError> {
+        let mut results = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut page_params = query_params.to_vec();
+            if let Some(after) = cursor.take() {
+                page_params.push(("after".to_string(), after));
+            }
+
+            let page: TwitchDataWrapper<T> = self.get_helix(path, &page_params, context).await?;
+            let page_len = page.data.len();
+            results.extend(page.data);
+
+            match page.pagination.and_then(|p| p.cursor) {
+                Some(next) if !next.is_empty() && page_len > 0 => {
+                    trace!(path, "Following pagination cursor");
+                    cursor = Some(next);
+                }
+                _ => return Ok(results),
+            }
+        }
+    }
 
-        if response.status().is_success() {
-            let user_data: TwitchDataWrapper<User> = response.json().await?;
-            debug!("Received data for {} users", user_data.data.len());
-            Ok(user_data.data)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "<Failed to read error body>".to_string());
-            warn!(status = %status, error_body = %error_text, "Failed to get user data");
-            Err(ApiError::TwitchError {
-                status,
-                message: error_text,
+    /// Splits `values` into batches Helix accepts, queries each batch as `key=value`
+    /// parameters (plus `extra_params`) with bounded concurrency, and merges the results
+    /// in batch order.
+    async fn get_helix_batched<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        key: &str,
+        values: &[String],
+        extra_params: &[(String, String)],
+        context: &str,
+    ) -> Result<Vec<T>, ApiError> {
+        let batches: Vec<Vec<(String, String)>> = values
+            .chunks(MAX_IDS_PER_REQUEST)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|value| (key.to_string(), value.clone()))
+                    .chain(extra_params.iter().cloned())
+                    .collect()
+            })
+            .collect();
+
+        if batches.len() > 1 {
+            debug!(
+                path,
+                batches = batches.len(),
+                "Splitting request into batches"
+            );
+        }
+
+        stream::iter(batches)
+            .map(|query_params| async move {
+                self.get_helix_paginated::<T>(path, &query_params, context)
+                    .await
             })
+            .buffered(self.max_concurrent_requests)
+            .try_concat()
+            .await
+    }
+
+    /// Converts an unsuccessful response into an `ApiError::TwitchError`, logging it.
+    async fn error_from_response(response: reqwest::Response, context: &str) -> ApiError {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "<Failed to read error body>".to_string());
+        warn!(status = %status, error_body = %error_text, "{}", context);
+        ApiError::TwitchError {
+            status,
+            message: error_text,
         }
     }
 
+    /// Gets Twitch User information for a list of login names.
+    /// Lists longer than Twitch's 100-login limit are fetched in batches.
+    pub async fn get_users_by_login(&self, logins: &[String]) -> Result<Vec<User>, ApiError> {
+        if logins.is_empty() {
+            return Ok(vec![]); // Nothing to fetch
+        }
+
+        debug!(logins = ?logins, "Fetching user data from Twitch API");
+
+        // Query parameters: ?login=user1&login=user2...
+        let users: Vec<User> = self
+            .get_helix_batched("users", "login", logins, &[], "Failed to get user data")
+            .await?;
+        debug!("Received data for {} users", users.len());
+        Ok(users)
+    }
+
     /// Gets live Stream information for a list of user IDs.
     /// Note: This endpoint only returns currently live streams.
+    /// Lists longer than Twitch's 100-ID limit are fetched in batches.
     pub async fn get_streams_by_user_id(
         &self,
         user_ids: &[String],
@@ -203,54 +572,426 @@ impl TwitchClient {
             return Ok(vec![]); // Nothing to fetch
         }
 
-        let token = self.access_token.as_deref().ok_or(ApiError::MissingToken)?;
+        debug!(user_ids = ?user_ids, "Fetching stream data from Twitch API");
+
+        // Query parameters: ?user_id=123&user_id=456...
+        // `first` defaults to 20, so ask for full pages to keep pagination short
+        let page_size = [("first".to_string(), MAX_IDS_PER_REQUEST.to_string())];
+        let streams: Vec<Stream> = self
+            .get_helix_batched(
+                "streams",
+                "user_id",
+                user_ids,
+                &page_size,
+                "Failed to get stream data",
+            )
+            .await?;
+        debug!("Received data for {} live streams", streams.len());
+        Ok(streams)
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", token))
-                .expect("Failed to create auth header value"),
-        );
-        headers.insert(
-            "Client-Id",
-            HeaderValue::from_str(&self.client_id)
-                .expect("Failed to create client ID header value"),
+    /// Gets Channel Information (title and game) for a list of broadcaster IDs.
+    /// Works for offline channels too, which makes it useful when `/streams` lags behind.
+    pub async fn get_channel_information(
+        &self,
+        broadcaster_ids: &[String],
+    ) -> Result<Vec<ChannelInformation>, ApiError> {
+        if broadcaster_ids.is_empty() {
+            return Ok(vec![]); // Nothing to fetch
+        }
+
+        debug!(broadcaster_ids = ?broadcaster_ids, "Fetching channel information from Twitch API");
+
+        let channels: Vec<ChannelInformation> = self
+            .get_helix_batched(
+                "channels",
+                "broadcaster_id",
+                broadcaster_ids,
+                &[],
+                "Failed to get channel information",
+            )
+            .await?;
+        Ok(channels)
+    }
+
+    /// Creates an EventSub subscription for a broadcaster.
+    /// A subscription that already exists (409 Conflict) counts as success.
+    pub async fn create_eventsub_subscription(
+        &self,
+        subscription_type: &str,
+        version: &str,
+        broadcaster_user_id: &str,
+        transport: &EventSubTransport,
+    ) -> Result<(), ApiError> {
+        // Webhook subscriptions use the App Access Token, WebSocket ones a User Access Token
+        let user_token = match transport {
+            EventSubTransport::Websocket { .. } => Some(
+                self.user_access_token
+                    .as_deref()
+                    .ok_or(ApiError::MissingUserToken)?,
+            ),
+            EventSubTransport::Webhook { .. } => None,
+        };
+
+        let body = serde_json::to_value(CreateSubscriptionRequest {
+            subscription_type,
+            version,
+            condition: serde_json::json!({ "broadcaster_user_id": broadcaster_user_id }),
+            transport,
+        })?;
+
+        debug!(
+            subscription_type,
+            broadcaster_user_id, "Creating EventSub subscription"
         );
 
-        // Build the URL with query parameters: ?user_id=123&user_id=456...
-        let url = format!("{}/streams", TWITCH_API_BASE_URL);
-        let query_params: Vec<(String, String)> = user_ids
+        let result: Result<TwitchDataWrapper<EventSubSubscription>, ApiError> = self
+            .send_helix(
+                Method::POST,
+                &self.eventsub_subscriptions_url(),
+                &[],
+                Some(&body),
+                user_token,
+                "Failed to create EventSub subscription",
+            )
+            .await;
+
+        match result {
+            Ok(created) => {
+                for subscription in created.data {
+                    debug!(
+                        id = %subscription.id,
+                        status = %subscription.status,
+                        "Created EventSub subscription"
+                    );
+                }
+                Ok(())
+            }
+            Err(ApiError::TwitchError { status, .. }) if status == StatusCode::CONFLICT => {
+                debug!(
+                    subscription_type,
+                    broadcaster_user_id, "EventSub subscription already exists"
+                );
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, RawQuery, State};
+    use axum::http::HeaderMap as AxumHeaders;
+    use axum::routing::{get, post};
+    use axum::Router;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use tokio::net::TcpListener;
+
+    /// A Helix request the mock received.
+    struct HelixRequest {
+        path: String,
+        query: Vec<(String, String)>,
+        token: String,
+    }
+
+    /// Answers a Helix request with a status and JSON body.
+    type Respond = fn(&HelixRequest) -> (StatusCode, serde_json::Value);
+
+    /// A local stand-in for Twitch's OAuth and Helix endpoints. Tokens are handed out
+    /// as `token-1`, `token-2`, ...; Helix rejects the tokens listed in `rejected`.
+    struct MockTwitch {
+        expires_in: u64,
+        validate_status: StdMutex<StatusCode>,
+        rejected: StdMutex<Vec<String>>,
+        token_requests: StdMutex<u32>,
+        helix: StdMutex<Vec<HelixRequest>>,
+        respond: Respond,
+        // How long each Helix response takes, and the most requests seen at once
+        delay: StdMutex<Duration>,
+        in_flight: StdMutex<(usize, usize)>,
+    }
+
+    impl MockTwitch {
+        async fn start(expires_in: u64, respond: Respond) -> (Arc<Self>, TwitchClient) {
+            let mock = Arc::new(Self {
+                expires_in,
+                validate_status: StdMutex::new(StatusCode::OK),
+                rejected: StdMutex::default(),
+                token_requests: StdMutex::default(),
+                helix: StdMutex::default(),
+                respond,
+                delay: StdMutex::default(),
+                in_flight: StdMutex::default(),
+            });
+            let router = Router::new()
+                .route("/oauth2/token", post(Self::token))
+                .route("/oauth2/validate", get(Self::validate))
+                .route("/helix/{*path}", get(Self::helix))
+                .with_state(mock.clone());
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, router).await });
+
+            let client = TwitchClient::new("client-id".to_string(), "secret".to_string())
+                .unwrap()
+                .with_api_base_url(format!("http://{}/helix", addr))
+                .with_auth_base_url(format!("http://{}/oauth2/", addr));
+            (mock, client)
+        }
+
+        async fn token(State(mock): State<Arc<Self>>) -> String {
+            let mut requests = mock.token_requests.lock().unwrap();
+            *requests += 1;
+            serde_json::json!({
+                "access_token": format!("token-{}", requests),
+                "expires_in": mock.expires_in,
+                "token_type": "bearer"
+            })
+            .to_string()
+        }
+
+        async fn validate(State(mock): State<Arc<Self>>) -> (StatusCode, String) {
+            let status = *mock.validate_status.lock().unwrap();
+            let body =
+                serde_json::json!({ "client_id": "client-id", "expires_in": mock.expires_in });
+            (status, body.to_string())
+        }
+
+        async fn helix(
+            State(mock): State<Arc<Self>>,
+            Path(path): Path<String>,
+            RawQuery(query): RawQuery,
+            headers: AxumHeaders,
+        ) -> (StatusCode, String) {
+            let token = headers[AUTHORIZATION]
+                .to_str()
+                .unwrap()
+                .trim_start_matches("Bearer ")
+                .to_string();
+            let query = reqwest::Url::parse(&format!("http://mock/?{}", query.unwrap_or_default()))
+                .unwrap()
+                .query_pairs()
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect();
+            let request = HelixRequest { path, query, token };
+
+            {
+                let mut in_flight = mock.in_flight.lock().unwrap();
+                in_flight.0 += 1;
+                in_flight.1 = in_flight.1.max(in_flight.0);
+            }
+            let delay = *mock.delay.lock().unwrap();
+            tokio::time::sleep(delay).await;
+            mock.in_flight.lock().unwrap().0 -= 1;
+
+            let response = if mock.rejected.lock().unwrap().contains(&request.token) {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    serde_json::json!({ "message": "Invalid OAuth token" }),
+                )
+            } else {
+                (mock.respond)(&request)
+            };
+            mock.helix.lock().unwrap().push(request);
+            (response.0, response.1.to_string())
+        }
+
+        fn token_requests(&self) -> u32 {
+            *self.token_requests.lock().unwrap()
+        }
+
+        fn tokens_used(&self) -> Vec<String> {
+            let helix = self.helix.lock().unwrap();
+            helix.iter().map(|r| r.token.clone()).collect()
+        }
+    }
+
+    /// Answers `/users` with a user for each requested login.
+    fn users(request: &HelixRequest) -> (StatusCode, serde_json::Value) {
+        let data: Vec<_> = request
+            .query
             .iter()
-            .map(|id| ("user_id".to_string(), id.clone()))
+            .filter(|(key, _)| key == "login")
+            .map(|(_, login)| {
+                serde_json::json!({ "id": format!("id-{}", login), "login": login, "display_name": login })
+            })
             .collect();
+        (StatusCode::OK, serde_json::json!({ "data": data }))
+    }
 
-        debug!(user_ids = ?user_ids, "Fetching stream data from Twitch API");
+    /// Answers `/streams` with one stream per page, following the cursor to a third page.
+    fn paged_streams(request: &HelixRequest) -> (StatusCode, serde_json::Value) {
+        let after = request
+            .query
+            .iter()
+            .find(|(key, _)| key == "after")
+            .map(|(_, cursor)| cursor.as_str());
+        let (page, pagination) = match after {
+            None => (1, serde_json::json!({ "cursor": "page-2" })),
+            Some("page-2") => (2, serde_json::json!({ "cursor": "page-3" })),
+            // The last page may carry an empty cursor instead of none
+            _ => (3, serde_json::json!({ "cursor": "" })),
+        };
+        let stream = serde_json::json!({
+            "id": format!("stream-{}", page),
+            "user_id": format!("{}", page),
+            "user_login": format!("user{}", page),
+            "user_name": format!("User{}", page),
+            "game_id": "509658",
+            "game_name": "Just Chatting",
+            "title": "Hi",
+            "type": "live",
+            "viewer_count": 1,
+            "started_at": "2026-10-17T08:00:00Z"
+        });
+        (
+            StatusCode::OK,
+            serde_json::json!({ "data": [stream], "pagination": pagination }),
+        )
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .headers(headers)
-            .query(&query_params)
-            .send()
-            .await?;
+    fn no_streams(_: &HelixRequest) -> (StatusCode, serde_json::Value) {
+        (
+            StatusCode::OK,
+            serde_json::json!({ "data": [], "pagination": {} }),
+        )
+    }
 
-        if response.status().is_success() {
-            let stream_data: TwitchDataWrapper<Stream> = response.json().await?;
-            debug!("Received data for {} live streams", stream_data.data.len());
-            Ok(stream_data.data)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "<Failed to read error body>".to_string());
-            warn!(status = %status, error_body = %error_text, "Failed to get stream data");
-            Err(ApiError::TwitchError {
-                status,
-                message: error_text,
-            })
+    fn logins(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("user{}", i)).collect()
+    }
+
+    #[tokio::test]
+    async fn token_is_reused_until_it_nears_expiry() {
+        let (mock, client) = MockTwitch::start(3600, users).await;
+
+        client.get_users_by_login(&logins(1)).await.unwrap();
+        client.get_users_by_login(&logins(1)).await.unwrap();
+
+        assert_eq!(mock.token_requests(), 1);
+        assert_eq!(mock.tokens_used(), ["token-1", "token-1"]);
+    }
+
+    #[tokio::test]
+    async fn token_is_refreshed_within_the_expiry_margin() {
+        // Expiring sooner than `TOKEN_REFRESH_MARGIN`: every request needs a new one
+        let (mock, client) = MockTwitch::start(60, users).await;
+
+        client.get_users_by_login(&logins(1)).await.unwrap();
+        client.get_users_by_login(&logins(1)).await.unwrap();
+
+        assert_eq!(mock.token_requests(), 2);
+        assert_eq!(mock.tokens_used(), ["token-1", "token-2"]);
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_refreshed_and_the_request_retried_once() {
+        let (mock, client) = MockTwitch::start(3600, users).await;
+        mock.rejected.lock().unwrap().push("token-1".to_string());
+
+        let users = client.get_users_by_login(&logins(1)).await.unwrap();
+
+        assert_eq!(users.len(), 1);
+        assert_eq!(mock.tokens_used(), ["token-1", "token-2"]);
+    }
+
+    #[tokio::test]
+    async fn second_rejection_is_an_error() {
+        let (mock, client) = MockTwitch::start(3600, users).await;
+        mock.rejected
+            .lock()
+            .unwrap()
+            .extend(["token-1".to_string(), "token-2".to_string()]);
+
+        let result = client.get_users_by_login(&logins(1)).await;
+
+        assert!(matches!(
+            result,
+            Err(ApiError::TwitchError { status, .. }) if status == StatusCode::UNAUTHORIZED
+        ));
+        assert_eq!(mock.tokens_used().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_validation_replaces_the_token() {
+        let (mock, client) = MockTwitch::start(3600, users).await;
+        client.validate_token().await.unwrap();
+        assert_eq!(mock.token_requests(), 1);
+
+        // The hourly check finds the token revoked
+        *mock.validate_status.lock().unwrap() = StatusCode::UNAUTHORIZED;
+        client.validate_token().await.unwrap();
+        client.get_users_by_login(&logins(1)).await.unwrap();
+
+        assert_eq!(mock.token_requests(), 2);
+        assert_eq!(mock.tokens_used(), ["token-2"]);
+    }
+
+    #[tokio::test]
+    async fn long_id_lists_are_split_into_batches_of_100() {
+        let (mock, client) = MockTwitch::start(3600, no_streams).await;
+        let ids: Vec<String> = (0..250).map(|i| i.to_string()).collect();
+
+        client.get_streams_by_user_id(&ids).await.unwrap();
+
+        let helix = mock.helix.lock().unwrap();
+        let mut batch_sizes = Vec::new();
+        let mut requested = Vec::new();
+        for request in helix.iter() {
+            assert_eq!(request.path, "streams");
+            assert!(request
+                .query
+                .contains(&("first".to_string(), "100".to_string())));
+            let batch: Vec<&String> = request
+                .query
+                .iter()
+                .filter(|(key, _)| key == "user_id")
+                .map(|(_, id)| id)
+                .collect();
+            batch_sizes.push(batch.len());
+            requested.extend(batch.into_iter().cloned());
         }
+        batch_sizes.sort_unstable();
+        assert_eq!(batch_sizes, [50, 100, 100]);
+        requested.sort_unstable();
+        let mut expected = ids.clone();
+        expected.sort_unstable();
+        assert_eq!(requested, expected);
     }
-}
 
-// TODO: Add TwitchClient struct and methods
+    #[tokio::test]
+    async fn batches_respect_the_concurrency_limit_and_keep_their_order() {
+        let (mock, client) = MockTwitch::start(3600, users).await;
+        let client = client.with_max_concurrent_requests(2);
+        *mock.delay.lock().unwrap() = Duration::from_millis(100);
+        let logins = logins(500);
+
+        let users = client.get_users_by_login(&logins).await.unwrap();
+
+        assert_eq!(mock.helix.lock().unwrap().len(), 5);
+        assert_eq!(mock.in_flight.lock().unwrap().1, 2);
+        let returned: Vec<String> = users.into_iter().map(|u| u.login).collect();
+        assert_eq!(returned, logins);
+    }
+
+    #[tokio::test]
+    async fn pagination_cursor_is_followed_until_empty() {
+        let (mock, client) = MockTwitch::start(3600, paged_streams).await;
+
+        let streams = client
+            .get_streams_by_user_id(&["1".to_string()])
+            .await
+            .unwrap();
+
+        let ids: Vec<&str> = streams.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["stream-1", "stream-2", "stream-3"]);
+        let helix = mock.helix.lock().unwrap();
+        assert_eq!(helix.len(), 3);
+        // Every page repeats the original query
+        assert!(helix
+            .iter()
+            .all(|r| r.query.contains(&("user_id".to_string(), "1".to_string()))));
+    }
+}
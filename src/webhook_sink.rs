@@ -0,0 +1,589 @@
+// src/webhook_sink.rs
+
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, error, info, warn};
+
+use crate::notifications::{NotificationSink, StreamNotification};
+use crate::settings::{self, WebhookSinkConfig};
+use crate::templates::{self, Escape};
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Notifications waiting for the delivery worker; the on-disk queue holds the rest.
+const CHANNEL_CAPACITY: usize = 100;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Whether a body template renders JSON, judging by the configured `Content-Type`.
+fn escaping(config: &WebhookSinkConfig) -> Escape {
+    let content_type = config
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+        .map(|(_, value)| value.to_ascii_lowercase());
+    match content_type {
+        Some(content_type) if !content_type.contains("json") => Escape::None,
+        _ => Escape::Json,
+    }
+}
+
+/// Checks a webhook sink's settings when the config is loaded.
+pub fn validate(config: &WebhookSinkConfig) -> Result<(), String> {
+    if config.url.is_empty() {
+        return Err("webhook sink needs a url".to_string());
+    }
+    Method::from_bytes(config.method.to_uppercase().as_bytes())
+        .map_err(|_| format!("invalid webhook method '{}'", config.method))?;
+    header_map(config)?;
+    if config.hmac_secret.is_some() {
+        HeaderName::from_bytes(config.signature_header.as_bytes())
+            .map_err(|_| format!("invalid signature header '{}'", config.signature_header))?;
+    }
+    templates::validate(&config.body, escaping(config), true)
+        .map_err(|e| format!("webhook body: {}", e))
+}
+
+fn header_map(config: &WebhookSinkConfig) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+    if escaping(config) == Escape::Json {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    for (name, value) in &config.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid webhook header name '{}'", name))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("invalid value for webhook header '{}'", name))?;
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+/// Where a sink keeps its queue: `queue_file`, or a file in the cache directory named
+/// after the endpoint, so reordering sinks keeps their queues apart.
+pub fn queue_file(config: &WebhookSinkConfig) -> Option<PathBuf> {
+    config.queue_file.clone().or_else(|| {
+        let digest = hex::encode(Sha256::digest(config.url.as_bytes()));
+        settings::cache_dir().map(|dir| dir.join(format!("webhook-{}.jsonl", &digest[..12])))
+    })
+}
+
+/// Posts notifications to an HTTP endpoint, rendered from a body template.
+///
+/// Deliveries go through a bounded queue that is mirrored to disk, so events
+/// survive an outage of the endpoint (and a restart) as long as they fit.
+pub struct WebhookSink {
+    body: String,
+    escaping: Escape,
+    tx: mpsc::Sender<String>,
+    worker: JoinHandle<()>,
+}
+
+impl WebhookSink {
+    /// Starts the delivery worker. Must be called from within the Tokio runtime.
+    pub fn new(config: &WebhookSinkConfig) -> Self {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let worker = DeliveryWorker::new(config, queue_file(config));
+        let worker = tokio::spawn(worker.run(rx));
+
+        Self {
+            body: config.body.clone(),
+            escaping: escaping(config),
+            tx,
+            worker,
+        }
+    }
+}
+
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn send(&self, notification: &StreamNotification) {
+        let body = templates::render(&self.body, notification, self.escaping);
+        if let Err(e) = self.tx.try_send(body) {
+            warn!(
+                "(Webhook) Dropping notification, delivery worker is behind: {}",
+                e
+            );
+        }
+    }
+
+    /// Closing the channel lets the worker deliver what it was sent and stop.
+    fn shutdown(self: Box<Self>) -> Vec<JoinHandle<()>> {
+        let Self { worker, .. } = *self;
+        vec![worker]
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QueuedDelivery {
+    body: String,
+    // Unix timestamp, for dropping events that are too old to matter
+    queued_at: i64,
+}
+
+/// Deliveries that haven't gone through yet, oldest first.
+/// With a file, every change is written out so the queue survives restarts.
+#[derive(Debug)]
+struct DeliveryQueue {
+    file: Option<PathBuf>,
+    capacity: usize,
+    entries: VecDeque<QueuedDelivery>,
+}
+
+impl DeliveryQueue {
+    fn load(file: Option<PathBuf>, capacity: usize) -> Self {
+        let entries: VecDeque<QueuedDelivery> = file
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !entries.is_empty() {
+            info!(
+                "(Webhook) Resuming {} queued deliveries from a previous run",
+                entries.len()
+            );
+        }
+        Self {
+            file,
+            capacity,
+            entries,
+        }
+    }
+
+    async fn save(&self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+        let contents: String = self
+            .entries
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .map(|line| line + "\n")
+            .collect();
+        let tmp_path = path.with_extension("part");
+        let result = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&tmp_path, contents).await?;
+            tokio::fs::rename(&tmp_path, path).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!(
+                "(Webhook) Failed to write queue to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    async fn push(&mut self, body: String) {
+        if self.entries.len() >= self.capacity {
+            warn!("(Webhook) Queue is full, dropping the oldest delivery");
+            self.entries.pop_front();
+        }
+        self.entries.push_back(QueuedDelivery {
+            body,
+            queued_at: chrono::Utc::now().timestamp(),
+        });
+        self.save().await;
+    }
+
+    async fn pop_front(&mut self) {
+        self.entries.pop_front();
+        self.save().await;
+    }
+
+    async fn drop_expired(&mut self, max_age: Duration) {
+        let cutoff = chrono::Utc::now().timestamp() - max_age.as_secs() as i64;
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.queued_at >= cutoff);
+        let dropped = before - self.entries.len();
+        if dropped > 0 {
+            warn!("(Webhook) Dropped {} deliveries that were too old", dropped);
+            self.save().await;
+        }
+    }
+}
+
+/// The outcome of one delivery attempt.
+enum Delivery {
+    Delivered,
+    /// The endpoint refused it; retrying won't help.
+    Rejected,
+    /// Network error, timeout, 429 or 5xx: try again later.
+    Retry,
+}
+
+struct DeliveryWorker {
+    client: reqwest::Client,
+    method: Method,
+    url: String,
+    headers: HeaderMap,
+    // Header name and HMAC-SHA256 secret for signing bodies
+    signature: Option<(HeaderName, String)>,
+    timeout: Duration,
+    max_retry_delay: Duration,
+    max_age: Duration,
+    queue: DeliveryQueue,
+}
+
+impl DeliveryWorker {
+    fn new(config: &WebhookSinkConfig, queue_file: Option<PathBuf>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            // Both were checked by `validate` when the config was loaded
+            method: Method::from_bytes(config.method.to_uppercase().as_bytes())
+                .unwrap_or(Method::POST),
+            headers: header_map(config).unwrap_or_default(),
+            url: config.url.clone(),
+            signature: config.hmac_secret.clone().and_then(|secret| {
+                HeaderName::from_bytes(config.signature_header.as_bytes())
+                    .ok()
+                    .map(|header| (header, secret))
+            }),
+            timeout: Duration::from_secs(config.timeout_seconds),
+            max_retry_delay: Duration::from_secs(config.max_retry_delay_seconds),
+            max_age: Duration::from_secs(config.max_event_age_seconds),
+            queue: DeliveryQueue::load(queue_file, config.queue_capacity),
+        }
+    }
+
+    async fn run(mut self, mut rx: mpsc::Receiver<String>) {
+        let mut retry_delay = INITIAL_RETRY_DELAY;
+        let mut retry_at: Option<Instant> = None;
+
+        loop {
+            if retry_at.is_none() {
+                self.queue.drop_expired(self.max_age).await;
+                while let Some(entry) = self.queue.entries.front() {
+                    match self.deliver(&entry.body).await {
+                        Delivery::Delivered => {
+                            retry_delay = INITIAL_RETRY_DELAY;
+                            self.queue.pop_front().await;
+                        }
+                        Delivery::Rejected => self.queue.pop_front().await,
+                        Delivery::Retry => {
+                            debug!("(Webhook) Retrying in {}s", retry_delay.as_secs());
+                            retry_at = Some(Instant::now() + retry_delay);
+                            retry_delay = (retry_delay * 2).min(self.max_retry_delay);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            tokio::select! {
+                body = rx.recv() => match body {
+                    Some(body) => self.queue.push(body).await,
+                    // The sink is gone; what's left stays on disk for the next run
+                    None => break,
+                },
+                _ = sleep_until(retry_at.unwrap_or_else(Instant::now)), if retry_at.is_some() => {
+                    retry_at = None;
+                }
+            }
+        }
+    }
+
+    fn sign(&self, body: &str) -> Option<(HeaderName, String)> {
+        let (header, secret) = self.signature.as_ref()?;
+        let mut mac =
+            HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(body.as_bytes());
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        Some((header.clone(), signature))
+    }
+
+    async fn deliver(&self, body: &str) -> Delivery {
+        let mut request = self
+            .client
+            .request(self.method.clone(), &self.url)
+            .headers(self.headers.clone())
+            .timeout(self.timeout)
+            .body(body.to_string());
+        if let Some((header, signature)) = self.sign(body) {
+            request = request.header(header, signature);
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!(status = %response.status(), "(Webhook) Delivered");
+                Delivery::Delivered
+            }
+            Ok(response)
+                if response.status().is_server_error()
+                    || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                warn!(status = %response.status(), "(Webhook) Endpoint unavailable");
+                Delivery::Retry
+            }
+            Ok(response) => {
+                let status = response.status();
+                let message = response.text().await.unwrap_or_default();
+                error!(status = %status, "(Webhook) Delivery rejected: {}", message);
+                Delivery::Rejected
+            }
+            Err(e) => {
+                warn!("(Webhook) Delivery failed: {}", e);
+                Delivery::Retry
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::Router;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// A request the test endpoint received.
+    struct Received {
+        at: Instant,
+        headers: HeaderMap,
+        body: String,
+    }
+
+    /// An HTTP endpoint that answers with the queued statuses (then 200) and records requests.
+    #[derive(Default)]
+    struct Endpoint {
+        statuses: Mutex<VecDeque<StatusCode>>,
+        received: Mutex<Vec<Received>>,
+    }
+
+    impl Endpoint {
+        async fn start(statuses: &[StatusCode]) -> (Arc<Self>, String) {
+            let endpoint = Arc::new(Self {
+                statuses: Mutex::new(statuses.iter().copied().collect()),
+                received: Mutex::default(),
+            });
+            let router = Router::new()
+                .route("/hook", post(Self::handle))
+                .with_state(endpoint.clone());
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, router).await });
+            (endpoint, url)
+        }
+
+        async fn handle(
+            State(endpoint): State<Arc<Self>>,
+            headers: HeaderMap,
+            body: String,
+        ) -> StatusCode {
+            endpoint.received.lock().unwrap().push(Received {
+                at: Instant::now(),
+                headers,
+                body,
+            });
+            endpoint
+                .statuses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(StatusCode::OK)
+        }
+
+        /// Waits until `count` requests have come in.
+        async fn wait_for(&self, count: usize) {
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while self.received.lock().unwrap().len() < count {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("endpoint didn't receive the expected requests");
+        }
+
+        fn bodies(&self) -> Vec<String> {
+            let received = self.received.lock().unwrap();
+            received.iter().map(|r| r.body.clone()).collect()
+        }
+    }
+
+    fn config(url: &str) -> WebhookSinkConfig {
+        WebhookSinkConfig {
+            url: url.to_string(),
+            method: "POST".to_string(),
+            headers: HashMap::new(),
+            body: "{}".to_string(),
+            hmac_secret: None,
+            signature_header: "X-Signature-256".to_string(),
+            timeout_seconds: 5,
+            max_retry_delay_seconds: 300,
+            queue_capacity: 100,
+            max_event_age_seconds: 3600,
+            queue_file: None,
+        }
+    }
+
+    /// A queue file path unique to the test, removed first.
+    fn temp_queue_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "twitch-notifier-test-{}-{}.jsonl",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn write_queue(path: &PathBuf, entries: &[(&str, i64)]) {
+        let contents: String = entries
+            .iter()
+            .map(|(body, queued_at)| {
+                let entry = QueuedDelivery {
+                    body: body.to_string(),
+                    queued_at: *queued_at,
+                };
+                serde_json::to_string(&entry).unwrap() + "\n"
+            })
+            .collect();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[tokio::test]
+    async fn retries_with_exponential_backoff() {
+        let (endpoint, url) = Endpoint::start(&[
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::TOO_MANY_REQUESTS,
+        ])
+        .await;
+        let (tx, rx) = mpsc::channel(8);
+        let worker = tokio::spawn(DeliveryWorker::new(&config(&url), None).run(rx));
+
+        tx.send(r#"{"n": 1}"#.to_string()).await.unwrap();
+        endpoint.wait_for(3).await;
+        drop(tx);
+        worker.await.unwrap();
+
+        let received = endpoint.received.lock().unwrap();
+        assert!(received.iter().all(|r| r.body == r#"{"n": 1}"#));
+        let first_delay = received[1].at - received[0].at;
+        let second_delay = received[2].at - received[1].at;
+        assert!(first_delay >= INITIAL_RETRY_DELAY, "{:?}", first_delay);
+        assert!(
+            second_delay >= INITIAL_RETRY_DELAY * 2,
+            "{:?}",
+            second_delay
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_delivery_is_not_retried() {
+        let (endpoint, url) = Endpoint::start(&[StatusCode::BAD_REQUEST]).await;
+        let (tx, rx) = mpsc::channel(8);
+        let worker = tokio::spawn(DeliveryWorker::new(&config(&url), None).run(rx));
+
+        tx.send("first".to_string()).await.unwrap();
+        tx.send("second".to_string()).await.unwrap();
+        endpoint.wait_for(2).await;
+        drop(tx);
+        worker.await.unwrap();
+
+        assert_eq!(endpoint.bodies(), ["first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn queue_survives_restart() {
+        let path = temp_queue_file("resume");
+        let now = chrono::Utc::now().timestamp();
+
+        let mut queue = DeliveryQueue::load(Some(path.clone()), 10);
+        queue.push("first".to_string()).await;
+        queue.push("second".to_string()).await;
+        queue.pop_front().await;
+        let reloaded = DeliveryQueue::load(Some(path.clone()), 10);
+        assert_eq!(reloaded.entries.len(), 1);
+        assert_eq!(reloaded.entries[0].body, "second");
+        assert!(reloaded.entries[0].queued_at >= now);
+
+        // A worker started on the file delivers what was left before anything new
+        let (endpoint, url) = Endpoint::start(&[]).await;
+        let (tx, rx) = mpsc::channel(8);
+        let worker = tokio::spawn(DeliveryWorker::new(&config(&url), Some(path.clone())).run(rx));
+        tx.send("third".to_string()).await.unwrap();
+        endpoint.wait_for(2).await;
+        drop(tx);
+        worker.await.unwrap();
+
+        assert_eq!(endpoint.bodies(), ["second", "third"]);
+        assert!(DeliveryQueue::load(Some(path.clone()), 10)
+            .entries
+            .is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_oldest() {
+        let mut queue = DeliveryQueue::load(None, 2);
+        for body in ["first", "second", "third"] {
+            queue.push(body.to_string()).await;
+        }
+        let bodies: Vec<_> = queue.entries.iter().map(|e| e.body.as_str()).collect();
+        assert_eq!(bodies, ["second", "third"]);
+    }
+
+    #[tokio::test]
+    async fn expired_deliveries_are_dropped() {
+        let path = temp_queue_file("expiry");
+        let now = chrono::Utc::now().timestamp();
+        write_queue(&path, &[("stale", now - 7200), ("fresh", now - 60)]);
+
+        let (endpoint, url) = Endpoint::start(&[]).await;
+        let (tx, rx) = mpsc::channel(8);
+        let worker = tokio::spawn(DeliveryWorker::new(&config(&url), Some(path.clone())).run(rx));
+        endpoint.wait_for(1).await;
+        drop(tx);
+        worker.await.unwrap();
+
+        assert_eq!(endpoint.bodies(), ["fresh"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn bodies_are_signed() {
+        let (endpoint, url) = Endpoint::start(&[]).await;
+        let mut config = config(&url);
+        config.hmac_secret = Some("a-shared-secret".to_string());
+        let (tx, rx) = mpsc::channel(8);
+        let worker = tokio::spawn(DeliveryWorker::new(&config, None).run(rx));
+
+        let body = r#"{"event": "live"}"#;
+        tx.send(body.to_string()).await.unwrap();
+        endpoint.wait_for(1).await;
+        drop(tx);
+        worker.await.unwrap();
+
+        let mut mac = HmacSha256::new_from_slice(b"a-shared-secret").unwrap();
+        mac.update(body.as_bytes());
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        let received = endpoint.received.lock().unwrap();
+        assert_eq!(received[0].headers["x-signature-256"], expected.as_str());
+        assert_eq!(received[0].headers[CONTENT_TYPE], "application/json");
+    }
+}